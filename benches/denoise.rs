@@ -0,0 +1,132 @@
+use criterion::{
+    black_box,
+    criterion_group,
+    criterion_main,
+    BenchmarkId,
+    Criterion,
+};
+use image_recovery::{
+    ndarray::Array3,
+    ImageArray,
+    Layout,
+};
+
+// realistic image sizes, (width, height)
+const SIZES: [(usize, usize); 3] = [(400, 470), (1024, 768), (1920, 1080)];
+
+fn random_image_array(shape: (usize, usize, usize)) -> ImageArray<Array3<f64>> {
+    let mut array = Array3::<u8>::zeros(shape);
+    array.mapv_inplace(|_| rand::random::<u8>());
+    ImageArray::from(&array)
+}
+
+fn bench_denoise(c: &mut Criterion, name: &str, channels: usize) {
+    // same inputs as the `denoise` example
+    let tau: f64 = 1.0 / 2_f64.sqrt();
+    let sigma: f64 = 1_f64 / (8.0 * tau);
+    let lambda: f64 = 0.0259624705;
+    let gamma: f64 = 0.35 * lambda;
+    // a fixed number of iterations, the threshold is never hit
+    let max_iter: u32 = 10;
+    let convergence_threshold = 0.0;
+
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+    for (width, height) in SIZES {
+        for layout in [Layout::Interleaved, Layout::Planar] {
+            let image_array =
+                random_image_array((width, height, channels)).to_layout(layout);
+            group.bench_with_input(
+                BenchmarkId::new(
+                    format!("{:?}", layout),
+                    format!("{}x{}", width, height),
+                ),
+                &image_array,
+                |bench, image_array| {
+                    bench.iter(|| {
+                        black_box(
+                            image_array
+                                .denoise(
+                                    lambda,
+                                    tau,
+                                    sigma,
+                                    gamma,
+                                    max_iter,
+                                    convergence_threshold,
+                                )
+                                .unwrap(),
+                        )
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+fn denoise_gray(c: &mut Criterion) {
+    bench_denoise(c, "denoise_gray", 1);
+}
+
+fn denoise_rgb(c: &mut Criterion) {
+    bench_denoise(c, "denoise_rgb", 3);
+}
+
+fn denoise_algorithms(c: &mut Criterion) {
+    let tau: f64 = 1.0 / 2_f64.sqrt();
+    let sigma: f64 = 1_f64 / (8.0 * tau);
+    let lambda: f64 = 0.0259624705;
+    let gamma: f64 = 0.35 * lambda;
+    let max_iter: u32 = 10;
+    let convergence_threshold = 0.0;
+
+    let (width, height) = SIZES[1];
+    let image_array = random_image_array((width, height, 3));
+
+    let mut group = c.benchmark_group("denoise_algorithms");
+    group.sample_size(10);
+    group.bench_function("primal_dual", |bench| {
+        bench.iter(|| {
+            black_box(
+                image_array
+                    .denoise(
+                        lambda,
+                        tau,
+                        sigma,
+                        gamma,
+                        max_iter,
+                        convergence_threshold,
+                    )
+                    .unwrap(),
+            )
+        })
+    });
+    group.bench_function("primal_dual_adaptive", |bench| {
+        bench.iter(|| {
+            black_box(
+                image_array
+                    .denoise_adaptive(
+                        lambda,
+                        1.0,
+                        1.0,
+                        max_iter,
+                        convergence_threshold,
+                    )
+                    .unwrap(),
+            )
+        })
+    });
+    group.bench_function("dual_fista", |bench| {
+        bench.iter(|| {
+            black_box(
+                image_array
+                    .denoise_fista(lambda, max_iter, convergence_threshold)
+                    .unwrap(),
+            )
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, denoise_gray, denoise_rgb, denoise_algorithms);
+criterion_main!(benches);
@@ -0,0 +1,76 @@
+use criterion::{
+    black_box,
+    criterion_group,
+    criterion_main,
+    Criterion,
+};
+use image_recovery::{
+    ndarray::{
+        Array2,
+        Array3,
+    },
+    ops::{
+        Boundary,
+        Convolution,
+        Gradient,
+        VectorLen,
+    },
+};
+
+fn random_array3(shape: (usize, usize, usize)) -> Array3<f64> {
+    let mut array = Array3::zeros(shape);
+    array.mapv_inplace(|_| rand::random::<u8>() as f64);
+    array
+}
+
+fn array_f64_positive_gradient_on_axis(c: &mut Criterion) {
+    let a = random_array3((1024, 768, 3));
+
+    c.bench_function("array_f64_positive_gradient_on_axis", |bench| {
+        bench.iter(|| black_box(a.positive_gradient_on_axis(2).unwrap()))
+    });
+}
+
+fn array_f64_negative_gradient_on_axis(c: &mut Criterion) {
+    let a = random_array3((1024, 768, 3));
+
+    c.bench_function("array_f64_negative_gradient_on_axis", |bench| {
+        bench.iter(|| black_box(a.negative_gradient_on_axis(2).unwrap()))
+    });
+}
+
+fn array_f64_vector_len_on_axis(c: &mut Criterion) {
+    let a = random_array3((1024, 768, 3));
+    let b = random_array3((1024, 768, 3));
+
+    c.bench_function("array_f64_vector_len_on_axis", |bench| {
+        bench.iter(|| black_box(a.vector_len_on_axis(&b, 2).unwrap()))
+    });
+}
+
+fn array3_f64_convolve(c: &mut Criterion) {
+    let a = random_array3((1024, 768, 3));
+
+    // 5x5 kernels are convolved directly, 15x15 kernels with the FFT
+    for len in [5, 15] {
+        let kernel = Array2::from_elem((len, len), 1_f64 / (len * len) as f64);
+
+        c.bench_function(
+            &format!("array3_f64_convolve_{}x{}", len, len),
+            |bench| {
+                bench.iter(|| {
+                    black_box(a.convolve(&kernel, Boundary::Neumann).unwrap())
+                })
+            },
+        );
+    }
+}
+
+criterion_group!(
+    benches,
+    array_f64_positive_gradient_on_axis,
+    array_f64_negative_gradient_on_axis,
+    array_f64_vector_len_on_axis,
+    array3_f64_convolve
+);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+use image_recovery::{
+    image,      // re-exported `image` crate
+    ColorSpace, // color spaces the images can be denoised in
+    ImageArray, // struct for holding images
+};
+
+fn main() {
+    // the `image` crate provides functionality to decode images
+    let img = image::open("examples/source_images/angry_birb_noisy.png")
+        .expect("image could not be open")
+        .into_rgb8();
+
+    // transform the RGB image into a 3D Array
+    let image_array = ImageArray::from(&img);
+
+    // lambda drives the dual objective function
+    // closer to zero results in a smoother output image
+    // closer to infinity results in an output closer to the input
+    // in CIE L*a*b*, the lightness (luma) and the colors (chroma) can be
+    // denoised with different lambdas, and since the eye is less sensitive
+    // to noise in the colors, they can be smoothed harder
+    let luma_lambda: f64 = 0.0259624705;
+    let chroma_lambda: f64 = 0.25 * luma_lambda;
+
+    // choose bounds for denoising solver
+    // the algorithm will run for at most `max_iter` iterations
+    let max_iter: u32 = 500;
+
+    // the algorithm will stop running if:
+    // `convergence_threshold < norm(current - previous) / norm(previous)`
+    // where `current` is the output candidate for the current iteration,
+    // and `previous` is the output candidate of the previous iteration.
+    let convergence_threshold = 10_f64.powi(-10);
+
+    // now we can call the denoising solver in the L*a*b* color space, the
+    // output is converted back into RGB
+    let denoised_array = image_array
+        .denoise_in_color_space(
+            ColorSpace::Lab,
+            luma_lambda,
+            chroma_lambda,
+            |image_array, lambda| {
+                image_array.denoise_fista(
+                    lambda,
+                    max_iter,
+                    convergence_threshold,
+                )
+            },
+        )
+        .unwrap(); // will fail if image shape is 1 pixel in either x or y
+
+    // we convert the solution into an RGB image format
+    let denoised_img = denoised_array.into_rgb();
+
+    // encode it and save it to a file
+    denoised_img
+        .save("examples/result_images/angry_birb_denoised_in_lab.png")
+        .expect("image could not be saved");
+}
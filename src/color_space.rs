@@ -0,0 +1,286 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Conversion of the colors of the arrays between RGB and color spaces in
+//! which luma and chroma are decorrelated.
+use ndarray::{
+    ArrayViewMut3,
+    Axis,
+};
+
+/// Color space of the 3 color channels of an `ImageArray`, see
+/// [`to_color_space`](crate::ImageArray::to_color_space). The first channel
+/// of every color space other than RGB is luma (or lightness), and the other
+/// two are chroma. The channels are scaled to the range of the values of the
+/// image (e.g. `0.0..=255.0` for 8-bit images), so that the same `lambda`
+/// can be used in every color space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Red, green and blue, i.e. the values of the pixels.
+    #[default]
+    Rgb,
+    /// Luma and the blue and red differences of JPEG (ITU-R BT.601, full
+    /// range), where the chroma channels are offset by half of the range.
+    YCbCr,
+    /// CIE L\*a\*b\* of the sRGB colors (D65 white point), scaled by 1/100 of
+    /// the range, i.e. `L*` is in the range of the values of the image.
+    Lab,
+    /// Orthonormal opponent colors, i.e. `(R + G + B) / √3`,
+    /// `(R - G) / √2` and `(R + G - 2B) / √6`, which keeps white noise white.
+    Opponent,
+}
+
+/// Luma weights of ITU-R BT.601 for red and blue.
+const KR: f64 = 0.299;
+const KB: f64 = 0.114;
+
+/// Matrix from linear sRGB to CIE XYZ (D65).
+const RGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.1191920, 0.9503041],
+];
+
+/// Threshold of the linear part of the CIE L\*a\*b\* function.
+const LAB_DELTA: f64 = 6.0 / 29.0;
+
+impl ColorSpace {
+    /// Converts RGB colors with values in `0.0..=max` into this color space.
+    fn encode(&self, rgb: [f64; 3], max: f64) -> [f64; 3] {
+        match self {
+            Self::Rgb => rgb,
+            Self::YCbCr => {
+                let [y, cb, cr] = multiply(&ycbcr_matrix(), rgb);
+                [y, cb + max / 2.0, cr + max / 2.0]
+            },
+            Self::Lab => {
+                let linear = rgb.map(|x| srgb_to_linear(x / max));
+                let white = multiply(&RGB_TO_XYZ, [1.0; 3]);
+                let xyz = multiply(&RGB_TO_XYZ, linear);
+                let [fx, fy, fz] =
+                    [0, 1, 2].map(|i| lab_function(xyz[i] / white[i]));
+                [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+                    .map(|x| x * max / 100.0)
+            },
+            Self::Opponent => multiply(&opponent_matrix(), rgb),
+        }
+    }
+
+    /// Converts colors of this color space into RGB colors with values in
+    /// `0.0..=max`, i.e. the inverse of [`encode`](Self::encode).
+    fn decode(&self, color: [f64; 3], max: f64) -> [f64; 3] {
+        match self {
+            Self::Rgb => color,
+            Self::YCbCr => {
+                let [y, cb, cr] = color;
+                let ycbcr = [y, cb - max / 2.0, cr - max / 2.0];
+                multiply(&inverse(&ycbcr_matrix()), ycbcr)
+            },
+            Self::Lab => {
+                let [l, a, b] = color.map(|x| x * 100.0 / max);
+                let fy = (l + 16.0) / 116.0;
+                let f = [fy + a / 500.0, fy, fy - b / 200.0];
+                let white = multiply(&RGB_TO_XYZ, [1.0; 3]);
+                let xyz = [0, 1, 2].map(|i| lab_inverse(f[i]) * white[i]);
+                multiply(&inverse(&RGB_TO_XYZ), xyz)
+                    .map(|x| linear_to_srgb(x) * max)
+            },
+            Self::Opponent => multiply(&inverse(&opponent_matrix()), color),
+        }
+    }
+}
+
+/// Converts the colors (axis 2, of len 3) of the array with values in
+/// `0.0..=max` from one color space into another, in place.
+pub(crate) fn convert(
+    mut colors: ArrayViewMut3<'_, f64>,
+    from: ColorSpace,
+    to: ColorSpace,
+    max: f64,
+) {
+    if from == to {
+        return;
+    }
+
+    for mut pixel in colors.lanes_mut(Axis(2)) {
+        let rgb = from.decode([pixel[0], pixel[1], pixel[2]], max);
+        let converted = to.encode(rgb, max);
+        for (value, converted) in pixel.iter_mut().zip(converted) {
+            *value = converted;
+        }
+    }
+}
+
+fn ycbcr_matrix() -> [[f64; 3]; 3] {
+    let kg = 1.0 - KR - KB;
+    [
+        [KR, kg, KB],
+        [-0.5 * KR / (1.0 - KB), -0.5 * kg / (1.0 - KB), 0.5],
+        [0.5, -0.5 * kg / (1.0 - KR), -0.5 * KB / (1.0 - KR)],
+    ]
+}
+
+fn opponent_matrix() -> [[f64; 3]; 3] {
+    let (s2, s3, s6) = (2_f64.sqrt(), 3_f64.sqrt(), 6_f64.sqrt());
+    [
+        [1.0 / s3, 1.0 / s3, 1.0 / s3],
+        [1.0 / s2, -1.0 / s2, 0.0],
+        [1.0 / s6, 1.0 / s6, -2.0 / s6],
+    ]
+}
+
+fn multiply(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row.iter().zip(vector).map(|(a, b)| a * b).sum())
+}
+
+/// Inverse of an invertible 3x3 matrix, i.e. its adjugate divided by its
+/// determinant.
+fn inverse(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let cofactor = |i: usize, j: usize| {
+        let (i1, i2) = ((i + 1) % 3, (i + 2) % 3);
+        let (j1, j2) = ((j + 1) % 3, (j + 2) % 3);
+        m[i1][j1] * m[i2][j2] - m[i1][j2] * m[i2][j1]
+    };
+    let determinant: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| cofactor(j, i) / determinant))
+}
+
+/// sRGB transfer function, from gamma-encoded to linear values in
+/// `0.0..=1.0`, extended to negative values by symmetry so that it is
+/// invertible everywhere.
+fn srgb_to_linear(x: f64) -> f64 {
+    let linear = if x.abs() <= 0.04045 {
+        x.abs() / 12.92
+    } else {
+        ((x.abs() + 0.055) / 1.055).powf(2.4)
+    };
+    linear.copysign(x)
+}
+
+/// Inverse of [`srgb_to_linear`].
+fn linear_to_srgb(x: f64) -> f64 {
+    let srgb = if x.abs() <= 0.04045 / 12.92 {
+        x.abs() * 12.92
+    } else {
+        1.055 * x.abs().powf(1.0 / 2.4) - 0.055
+    };
+    srgb.copysign(x)
+}
+
+/// Function of CIE L\*a\*b\*, a cube root with a linear part near 0.
+fn lab_function(t: f64) -> f64 {
+    if t > LAB_DELTA.powi(3) {
+        t.cbrt()
+    } else {
+        t / (3.0 * LAB_DELTA.powi(2)) + 4.0 / 29.0
+    }
+}
+
+/// Inverse of [`lab_function`].
+fn lab_inverse(f: f64) -> f64 {
+    if f > LAB_DELTA {
+        f.powi(3)
+    } else {
+        3.0 * LAB_DELTA.powi(2) * (f - 4.0 / 29.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{
+        s,
+        Array3,
+    };
+
+    use super::{
+        convert,
+        ColorSpace,
+    };
+
+    const COLOR_SPACES: [ColorSpace; 4] = [
+        ColorSpace::Rgb,
+        ColorSpace::YCbCr,
+        ColorSpace::Lab,
+        ColorSpace::Opponent,
+    ];
+
+    fn assert_close(a: &[f64], b: &[f64], tolerance: f64) {
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < tolerance, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn decode_of_encode_is_identity() {
+        for color_space in COLOR_SPACES {
+            for max in [1.0, 255.0, 65535.0] {
+                for _ in 0..100 {
+                    // including values slightly out of range
+                    let rgb = [(); 3]
+                        .map(|_| (rand::random::<f64>() * 1.2 - 0.1) * max);
+
+                    let encoded = color_space.encode(rgb, max);
+                    let decoded = color_space.decode(encoded, max);
+
+                    assert_close(&decoded, &rgb, 1e-9 * max);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn encode_gray_has_no_chroma() {
+        let gray = [100.0; 3];
+
+        let ycbcr = ColorSpace::YCbCr.encode(gray, 255.0);
+        let lab = ColorSpace::Lab.encode(gray, 255.0);
+        let opponent = ColorSpace::Opponent.encode(gray, 255.0);
+
+        assert_close(&ycbcr, &[100.0, 127.5, 127.5], 1e-9);
+        assert_close(&lab[1..], &[0.0, 0.0], 1e-9);
+        assert_close(&opponent, &[100.0 * 3_f64.sqrt(), 0.0, 0.0], 1e-9);
+    }
+
+    #[test]
+    fn encode_lab_of_white_and_red() {
+        let white = ColorSpace::Lab.encode([255.0; 3], 255.0);
+        let red = ColorSpace::Lab.encode([1.0, 0.0, 0.0], 1.0);
+
+        assert_close(&white, &[255.0, 0.0, 0.0], 1e-9);
+        // L*a*b* of sRGB red is about (53.24, 80.09, 67.20)
+        assert_close(&red, &[0.5324, 0.8009, 0.6720], 1e-3);
+    }
+
+    #[test]
+    fn convert_between_color_spaces() {
+        let mut array = Array3::<f64>::zeros((4, 3, 4));
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let alpha = array.slice(s![.., .., 3]).to_owned();
+
+        let mut converted = array.clone();
+        let colors = converted.slice_mut(s![.., .., ..3]);
+        convert(colors, ColorSpace::Rgb, ColorSpace::Lab, 255.0);
+        let lab = converted.clone();
+        let colors = converted.slice_mut(s![.., .., ..3]);
+        convert(colors, ColorSpace::Lab, ColorSpace::YCbCr, 255.0);
+        let colors = converted.slice_mut(s![.., .., ..3]);
+        convert(colors, ColorSpace::YCbCr, ColorSpace::Rgb, 255.0);
+
+        assert!((&lab - &array).mapv(f64::abs).sum() > 1.0);
+        assert!((&converted - &array).mapv(f64::abs).sum() < 1e-8);
+        assert_eq!(converted.slice(s![.., .., 3]), alpha);
+    }
+}
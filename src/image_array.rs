@@ -1,32 +1,80 @@
-use std::ops::Deref;
+use std::{
+    borrow::Cow,
+    ops::{
+        Deref,
+        Range,
+    },
+};
 
 use image::{
+    ColorType,
+    DynamicImage,
     GrayImage,
+    ImageBuffer,
     Luma,
+    Pixel,
+    PixelWithColorType,
+    Primitive,
     Rgb,
+    Rgb32FImage,
     RgbImage,
+    RgbaImage,
 };
 use ndarray::{
+    s,
     Array,
     Array3,
+    ArrayView,
+    ArrayView1,
     Axis,
     Dimension,
     RemoveAxis,
+    ShapeBuilder,
+    ShapeError,
 };
 
-use crate::ops::{
-    Average,
-    Gradient,
-    Norm,
-    VectorLen,
+use crate::{
+    color_space::{
+        convert,
+        ColorSpace,
+    },
+    normalization::{
+        Normalization,
+        NormalizationTransform,
+    },
+    ops::{
+        Average,
+        Boundary,
+        Gradient,
+        Norm,
+        VectorLen,
+    },
+    options::{
+        AlphaPolicy,
+        SolverOptions,
+        TotalVariation,
+    },
+    quantization::{
+        quantize,
+        ClippedCount,
+        Quantization,
+        Subpixel,
+    },
 };
 
-/// An array representing an image, used with the solvers.
-/// The From trait is implemented for the types GrayImage and RgbImage in the
-/// [`image`](docs.rs/image/latest/image/) crate.
+/// An array representing an image, used with the solvers, along with the
+/// settings of the solvers, see [`options`](Self::options).
+/// The From trait is implemented for the images of the
+/// [`image`](docs.rs/image/latest/image/) crate and for `ndarray` arrays,
+/// and the array is converted back into images with the `into_*` methods.
 #[derive(Debug, Clone)]
 pub struct ImageArray<T: Gradient + Average + VectorLen + Norm> {
     inner: T,
+    options: SolverOptions<T>,
+    color_type: Option<ColorType>,
+    quantization: Quantization,
+    color_space: ColorSpace,
+    normalization: Option<NormalizationTransform>,
 }
 
 impl<T: Gradient + Average + VectorLen + Norm> Deref for ImageArray<T> {
@@ -37,96 +85,926 @@ impl<T: Gradient + Average + VectorLen + Norm> Deref for ImageArray<T> {
     }
 }
 
+/// Memory layout of an `ImageArray<Array3<f64>>`. The logical axes of the
+/// array are always `(x, y, channel)`, the layout only changes the order in
+/// which the elements are stored in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Memory order `(x, y, channel)`, i.e. the channels of each pixel are
+    /// contiguous (row-major order of the logical axes).
+    Interleaved,
+    /// Memory order `(channel, y, x)`, i.e. each channel is a contiguous
+    /// plane of contiguous rows (column-major order of the logical axes).
+    /// The shifts on the x and y axes performed by the solvers are faster
+    /// on this layout.
+    Planar,
+}
+
+/// Weights of the colors for converting an image to gray, see
+/// [`into_luma_with_weights`](ImageArray::into_luma_with_weights).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LumaWeights {
+    /// Luma of ITU-R BT.601 (SDTV), `0.299 R + 0.587 G + 0.114 B`.
+    Rec601,
+    /// Luma of ITU-R BT.709 (HDTV and sRGB), `0.2126 R + 0.7152 G + 0.0722
+    /// B`, which is also used by the `image` crate.
+    #[default]
+    Rec709,
+    /// Mean of all the color channels.
+    Mean,
+    /// Sum of all the color channels, saturated to the maximum value of the
+    /// output. The sum is converted with the [`Quantization`] of the image
+    /// like the other weights, i.e. rounded by default, while previous
+    /// versions truncated it, which [`Quantization::Truncate`] reproduces.
+    SaturatingSum,
+}
+
+/// Records the 8-bit color type, as for a `DynamicImage`.
 impl From<&GrayImage> for ImageArray<Array3<f64>> {
     fn from(value: &GrayImage) -> Self {
-        let dim = (value.width() as usize, value.height() as usize, 1);
-        let mut array = Array3::<f64>::zeros(dim);
-        for x in 0..dim.0 {
-            for y in 0..dim.1 {
-                let pixel = value.get_pixel(x as u32, y as u32);
-                array[[x, y, 0]] = pixel[0] as f64;
-            }
-        }
-
-        Self { inner: array }
+        let mut image_array = Self::new(array_from_image(value));
+        image_array.color_type = Some(ColorType::L8);
+        image_array
     }
 }
 
+/// Records the 8-bit color type, as for a `DynamicImage`.
 impl From<&RgbImage> for ImageArray<Array3<f64>> {
     fn from(value: &RgbImage) -> Self {
-        let dim = (value.width() as usize, value.height() as usize, 3);
-        let mut array = Array3::<f64>::zeros(dim);
-        for x in 0..dim.0 {
-            for y in 0..dim.1 {
-                let pixel = value.get_pixel(x as u32, y as u32);
-                for z in 0..3 {
-                    array[[x, y, z]] = pixel[z] as f64;
-                }
-            }
+        let mut image_array = Self::new(array_from_image(value));
+        image_array.color_type = Some(ColorType::Rgb8);
+        image_array
+    }
+}
+
+/// The alpha channel is the last channel of the array, and is left
+/// untouched by the solvers unless set otherwise with
+/// [`with_alpha_policy`](ImageArray::with_alpha_policy). Records the 8-bit
+/// color type, as for a `DynamicImage`.
+impl From<&RgbaImage> for ImageArray<Array3<f64>> {
+    fn from(value: &RgbaImage) -> Self {
+        let mut image_array = Self::new(array_from_image(value))
+            .with_alpha_policy(AlphaPolicy::default());
+        image_array.color_type = Some(ColorType::Rgba8);
+        image_array
+    }
+}
+
+/// Records the color type of the image, which is used by
+/// [`into_dynamic`](ImageArray::into_dynamic) to convert the array back into
+/// an image of the same type and bit depth. The values of the array are the
+/// values of the pixels, as for the other conversions from images, and the
+/// alpha channel (if any) is the last channel of the array, see
+/// [`AlphaPolicy`].
+impl From<&DynamicImage> for ImageArray<Array3<f64>> {
+    fn from(value: &DynamicImage) -> Self {
+        let array = match value {
+            DynamicImage::ImageLuma8(image) => array_from_image(image),
+            DynamicImage::ImageLumaA8(image) => array_from_image(image),
+            DynamicImage::ImageRgb8(image) => array_from_image(image),
+            DynamicImage::ImageRgba8(image) => array_from_image(image),
+            DynamicImage::ImageLuma16(image) => array_from_image(image),
+            DynamicImage::ImageLumaA16(image) => array_from_image(image),
+            DynamicImage::ImageRgb16(image) => array_from_image(image),
+            DynamicImage::ImageRgba16(image) => array_from_image(image),
+            DynamicImage::ImageRgb32F(image) => array_from_image(image),
+            DynamicImage::ImageRgba32F(image) => array_from_image(image),
+            // any other type can be stored as 32-bit float RGBA
+            _ => {
+                let image = DynamicImage::ImageRgba32F(value.to_rgba32f());
+                return Self::from(&image);
+            },
+        };
+
+        let mut image_array = Self::new(array);
+        image_array.color_type = Some(value.color());
+        if value.color().has_alpha() {
+            image_array.options = image_array
+                .options
+                .with_alpha_policy(AlphaPolicy::default());
         }
+        image_array
+    }
+}
 
-        Self { inner: array }
+/// Keeps the 16-bit values, i.e. the array values are in `0.0..=65535.0`,
+/// and records the 16-bit color type, as for a `DynamicImage`.
+impl From<&ImageBuffer<Luma<u16>, Vec<u16>>> for ImageArray<Array3<f64>> {
+    fn from(value: &ImageBuffer<Luma<u16>, Vec<u16>>) -> Self {
+        let mut image_array = Self::new(array_from_image(value));
+        image_array.color_type = Some(ColorType::L16);
+        image_array
     }
 }
 
+/// Keeps the 16-bit values, i.e. the array values are in `0.0..=65535.0`,
+/// and records the 16-bit color type, as for a `DynamicImage`.
+impl From<&ImageBuffer<Rgb<u16>, Vec<u16>>> for ImageArray<Array3<f64>> {
+    fn from(value: &ImageBuffer<Rgb<u16>, Vec<u16>>) -> Self {
+        let mut image_array = Self::new(array_from_image(value));
+        image_array.color_type = Some(ColorType::Rgb16);
+        image_array
+    }
+}
+
+/// Keeps the memory layout of the input if it is contiguous.
 impl<T: Copy + Into<f64>, D: Dimension + RemoveAxis> From<&Array<T, D>>
     for ImageArray<Array<f64, D>>
 {
     fn from(value: &Array<T, D>) -> Self {
+        Self::from(value.view())
+    }
+}
+
+/// Keeps the memory layout of the input if it is contiguous. The values are
+/// converted into a new owned array of `f64`, i.e. the view is copied, as
+/// `ImageArray` does not borrow its array. An array of `f64` can be moved
+/// into an `ImageArray` instead, see
+/// [`denoise_in_place`](ImageArray::denoise_in_place).
+impl<T: Copy + Into<f64>, D: Dimension + RemoveAxis> From<ArrayView<'_, T, D>>
+    for ImageArray<Array<f64, D>>
+{
+    fn from(value: ArrayView<'_, T, D>) -> Self {
+        Self::new(value.map(|&v| <T as Into<f64>>::into(v)))
+    }
+}
+
+/// Takes ownership of the array, without copying it.
+impl<D: Dimension + RemoveAxis> From<Array<f64, D>>
+    for ImageArray<Array<f64, D>>
+{
+    fn from(value: Array<f64, D>) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Gradient + Average + VectorLen + Norm> ImageArray<T> {
+    /// Wraps the given array with the default settings.
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            options: SolverOptions::default(),
+            color_type: None,
+            quantization: Quantization::default(),
+            color_space: ColorSpace::default(),
+            normalization: None,
+        }
+    }
+
+    /// Returns the inner array, without copying it.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Returns the settings of the solvers, which are also set with the
+    /// `with_*` methods of the image.
+    pub fn options(&self) -> &SolverOptions<T> {
+        &self.options
+    }
+
+    /// Sets all the settings of the solvers at once, replacing the ones set
+    /// with the other `with_*` methods.
+    pub fn with_options(mut self, options: SolverOptions<T>) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Returns the boundary condition used by the solvers.
+    pub fn boundary(&self) -> Boundary {
+        self.options.boundary()
+    }
+
+    /// Sets the boundary condition used by the solvers, see
+    /// [`SolverOptions::with_boundary`].
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.options = self.options.with_boundary(boundary);
+        self
+    }
+
+    /// Returns the total variation used by the solvers.
+    pub fn total_variation(&self) -> TotalVariation {
+        self.options.total_variation()
+    }
+
+    /// Sets the total variation used by the solvers.
+    pub fn with_total_variation(
+        mut self,
+        total_variation: TotalVariation,
+    ) -> Self {
+        self.options = self.options.with_total_variation(total_variation);
+        self
+    }
+
+    /// Returns the lambda map used by the solvers, if any.
+    pub fn lambda_map(&self) -> Option<&T> {
+        self.options.lambda_map()
+    }
+
+    /// Sets a lambda map used by the solvers, see
+    /// [`SolverOptions::with_lambda_map`].
+    pub fn with_lambda_map(mut self, lambda_map: T) -> Self {
+        self.options = self.options.with_lambda_map(lambda_map);
+        self
+    }
+
+    /// Removes the lambda map, so that the solvers use the same `lambda`
+    /// for every pixel.
+    pub fn without_lambda_map(mut self) -> Self {
+        self.options = self.options.without_lambda_map();
+        self
+    }
+
+    /// Returns the edge weights of the total variation used by the solvers,
+    /// if set with [`with_edge_weights`](Self::with_edge_weights).
+    pub fn edge_weights(&self) -> Option<&T> {
+        self.options.edge_weights()
+    }
+
+    /// Sets the edge weights of the total variation used by the solvers, see
+    /// [`SolverOptions::with_edge_weights`].
+    pub fn with_edge_weights(mut self, edge_weights: T) -> Self {
+        self.options = self.options.with_edge_weights(edge_weights);
+        self
+    }
+
+    /// Removes the edge weights (and the guide, if any), so that the total
+    /// variation is the same for every pixel.
+    pub fn without_edge_weights(mut self) -> Self {
+        self.options = self.options.without_edge_weights();
+        self
+    }
+
+    /// Returns how the solvers handle the last channel as alpha, or `None`
+    /// if the image has no alpha channel.
+    pub fn alpha_policy(&self) -> Option<AlphaPolicy> {
+        self.options.alpha_policy()
+    }
+
+    /// Sets how the solvers handle the alpha channel, i.e. the last channel
+    /// of the image, which is expected in `0.0..=255.0`, or up to the
+    /// maximum value of the color type of the image if it was converted from
+    /// an image (e.g. `65535.0` for 16-bit images).
+    pub fn with_alpha_policy(mut self, alpha_policy: AlphaPolicy) -> Self {
+        self.options = self.options.with_alpha_policy(alpha_policy);
+        self
+    }
+
+    /// Sets the mask of the observed values of the image used by the
+    /// solvers, see `SolverOptions::with_observation_mask`.
+    pub(crate) fn with_observation_mask(mut self, mask: T) -> Self {
+        self.options = self.options.with_observation_mask(mask);
+        self
+    }
+
+    /// Returns the color type of the image it was converted from, or `None`
+    /// if it was created from an array (e.g. by
+    /// [`read_pfm`](Self::read_pfm)), whose range is not known.
+    pub fn color_type(&self) -> Option<ColorType> {
+        self.color_type
+    }
+
+    /// Maximum value of the color type of the image, or of 8-bit images if
+    /// there is no color type, i.e. the value of white, and of a fully
+    /// opaque pixel in the alpha channel.
+    pub(crate) fn max_value(&self) -> f64 {
+        match self.color_type {
+            Some(ColorType::Rgb32F | ColorType::Rgba32F) => 1.0,
+            Some(color_type) => {
+                let bits = color_type.bits_per_pixel()
+                    / color_type.channel_count() as u16;
+                2_f64.powi(bits as i32) - 1.0
+            },
+            None => u8::MAX as f64,
+        }
+    }
+
+    /// Wraps the given array with the same settings (solver options, color
+    /// type, quantization, color space, normalization) as `self`. The lambda
+    /// map and edge weights of the options are shared, not copied.
+    pub(crate) fn with_inner(&self, inner: T) -> Self
+    where
+        T: Clone,
+    {
         Self {
-            inner: value.map(|&v| <T as Into<f64>>::into(v)),
+            inner,
+            options: self.options.clone(),
+            color_type: self.color_type,
+            quantization: self.quantization,
+            color_space: self.color_space,
+            normalization: self.normalization,
         }
     }
+
+    /// Returns the inner array mutably, keeping the settings of `self`.
+    pub(crate) fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
 }
 
 impl ImageArray<Array3<f64>> {
-    /// Assumes Array3 axis 2 is colors, will flatten axis 2 if bigger than 1.
-    pub fn into_luma(&self) -> GrayImage {
-        let shape = self.shape();
-        let flat = self.map_axis(Axis(2), |v| v.sum() as u8);
-        let mut img = GrayImage::new(shape[0] as u32, shape[1] as u32);
-        for x in 0..shape[0] {
-            for y in 0..shape[1] {
-                let pixel = Luma([flat[[x, y]]]);
-                img.put_pixel(x as u32, y as u32, pixel);
-            }
+    /// Wraps the given array of RGB colors, reconstructed from `self` which
+    /// is a gray image (e.g. the raw data of a sensor), with the same
+    /// settings as `self`, and the RGB color type of the same bit depth if
+    /// `self` was converted from a gray image.
+    pub(crate) fn with_rgb_inner(&self, inner: Array3<f64>) -> Self {
+        let color_type = match self.color_type {
+            Some(ColorType::L8) => Some(ColorType::Rgb8),
+            Some(ColorType::L16) => Some(ColorType::Rgb16),
+            _ => None,
+        };
+
+        Self {
+            options: self.options.clone().without_alpha_policy(),
+            color_type,
+            color_space: ColorSpace::Rgb,
+            ..self.with_inner(inner)
         }
-        img
+    }
+
+    /// Returns the memory layout of the array, or `None` if the array is not
+    /// stored in either of the supported layouts (e.g. if it was created
+    /// from an `Array3` with permuted axes). Arrays converted from images
+    /// are stored with the [`Layout::Planar`] memory layout.
+    pub fn layout(&self) -> Option<Layout> {
+        if self.t().is_standard_layout() {
+            Some(Layout::Planar)
+        } else if self.is_standard_layout() {
+            Some(Layout::Interleaved)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a copy of the array in the given memory layout. The values
+    /// (and their logical indexes) are the same in both arrays.
+    pub fn to_layout(&self, layout: Layout) -> Self {
+        let column_major = layout == Layout::Planar;
+        let mut array =
+            Array3::<f64>::zeros(self.raw_dim().set_f(column_major));
+        array.assign(&self.inner);
+
+        self.with_inner(array)
+    }
+
+    /// Sets the edge weights of the total variation from a guide image, so
+    /// that structure from the guide is transferred into the denoised image,
+    /// e.g. from a registered high-SNR channel or from an RGB image for a
+    /// depth map. The guide must have the same shape as `self` on the x and
+    /// y axes, but can have any number of channels, and `edge_scale` must be
+    /// positive and finite, otherwise an error is returned. Replaces the
+    /// edge weights, if any.
+    ///
+    /// The weight of each pixel is `1 / (1 + (|∇g| / edge_scale)^2)`, where
+    /// `|∇g|` is the length of the gradient of the guide over all of its
+    /// channels. The weight is close to 1 where the guide is flat, and
+    /// reduced where it has strong edges (to 1/2 where `|∇g| ==
+    /// edge_scale`), so edges are smoothed less there. `edge_scale` is in
+    /// the units of the guide. The weights are computed by the solvers, with
+    /// the boundary condition of the image at that time, so the order in
+    /// which `with_guide` and [`with_boundary`](Self::with_boundary) are
+    /// called does not matter.
+    pub fn with_guide(
+        mut self,
+        guide: &Array3<f64>,
+        edge_scale: f64,
+    ) -> Result<Self, ShapeError> {
+        if guide.len_of(Axis(0)) != self.len_of(Axis(0))
+            || guide.len_of(Axis(1)) != self.len_of(Axis(1))
+        {
+            let incompatible = ndarray::ErrorKind::IncompatibleShape;
+            return Err(ShapeError::from_kind(incompatible));
+        }
+        if !(edge_scale.is_finite() && edge_scale > 0_f64) {
+            let unsupported = ndarray::ErrorKind::Unsupported;
+            return Err(ShapeError::from_kind(unsupported));
+        }
+
+        self.options = self.options.with_guide(guide.clone(), edge_scale);
+        Ok(self)
+    }
+
+    /// Edge weights of the guide of `self` (see
+    /// [`with_guide`](Self::with_guide)) with the current boundary
+    /// condition, of shape `(width, height, 1)`.
+    pub(crate) fn edge_weights_of_guide(
+        &self,
+        guide: &Array3<f64>,
+        edge_scale: f64,
+    ) -> Result<Array3<f64>, ShapeError> {
+        let gradient_a = guide
+            .positive_gradient_on_axis_with_boundary(0, self.boundary())?;
+        let gradient_b = guide
+            .positive_gradient_on_axis_with_boundary(1, self.boundary())?;
+
+        Ok(gradient_a
+            .vector_len_on_axis(&gradient_b, 2)?
+            .mapv(|x| 1_f64 / (1_f64 + (x / edge_scale).powi(2))))
+    }
+
+    /// Assumes Array3 axis 2 is colors, will flatten axis 2 if bigger than 1
+    /// with the default [`LumaWeights`] (Rec.709), see
+    /// [`into_luma_with_weights`](Self::into_luma_with_weights).
+    pub fn into_luma(&self) -> GrayImage {
+        self.into_luma_with_weights(LumaWeights::default())
+    }
+
+    /// Assumes Array3 axis 2 is colors, will flatten axis 2 if bigger than 1
+    /// with the given [`LumaWeights`]. The alpha channel (if an
+    /// [`AlphaPolicy`] is set) is ignored. The values are converted to `u8`
+    /// with the [`Quantization`] of the image.
+    pub fn into_luma_with_weights(&self, weights: LumaWeights) -> GrayImage {
+        self.to_luma_image(weights).0
     }
 
     /// Assumes Array3 axis 2 is colors, will use 3 first elements of axis 2 if
-    /// bigger than 3, or cycle through the elements if smaller than 3.
+    /// bigger than 3, or cycle through the elements if smaller than 3. The
+    /// values are converted to `u8` with the [`Quantization`] of the image.
     pub fn into_rgb(&self) -> RgbImage {
-        let shape = self.shape();
-        let flat = self.map_axis(Axis(2), |v| v.map(|&x| x as u8).to_vec());
-        let mut img = RgbImage::new(shape[0] as u32, shape[1] as u32);
-        for x in 0..shape[0] {
-            for y in 0..shape[1] {
-                let mut colors = flat[[x, y]].iter().cloned().cycle();
-                let pixel = Rgb([
-                    colors.next().unwrap(),
-                    colors.next().unwrap(),
-                    colors.next().unwrap(),
-                ]);
-                img.put_pixel(x as u32, y as u32, pixel);
+        self.to_image().0
+    }
+
+    /// Same as [`into_rgb`](Self::into_rgb), also returning the number of
+    /// values which were clipped to `0..=255`, e.g. to check the output of
+    /// the solvers without [`Quantization::ClampAndReport`] only logging
+    /// it. See [`into_dynamic_with_report`](Self::into_dynamic_with_report)
+    /// for the other color types.
+    pub fn into_rgb_with_report(&self) -> (RgbImage, ClippedCount) {
+        self.to_image()
+    }
+
+    /// Assumes Array3 axis 2 is colors, and the last element of axis 2 is
+    /// alpha if an [`AlphaPolicy`] is set, in which case the colors are the
+    /// other elements (used as in [`into_rgb`](Self::into_rgb)). Without an
+    /// alpha policy, all the elements are colors, and the output is fully
+    /// opaque.
+    pub fn into_rgba(&self) -> RgbaImage {
+        self.to_image().0
+    }
+
+    /// Converts the array into an image of the color type it was converted
+    /// from, with the same bit depth, see
+    /// [`color_type`](Self::color_type). If the array was not converted from
+    /// an image, the color type is 8-bit RGBA if an
+    /// [`AlphaPolicy`] is set, 8-bit gray if the array has one channel, and
+    /// 8-bit RGB otherwise. The channels are used as in
+    /// [`into_luma`](Self::into_luma) for gray images without alpha, and as
+    /// in [`into_rgba`](Self::into_rgba) otherwise.
+    pub fn into_dynamic(&self) -> DynamicImage {
+        self.into_dynamic_with_report().0
+    }
+
+    /// Same as [`into_dynamic`](Self::into_dynamic), also returning the
+    /// number of values which were clipped to the range of the color type,
+    /// which is always zero for float color types.
+    pub fn into_dynamic_with_report(&self) -> (DynamicImage, ClippedCount) {
+        let color_type = self.color_type.unwrap_or(
+            match (self.alpha_policy(), self.len_of(Axis(2))) {
+                (Some(_), _) => ColorType::Rgba8,
+                (None, 1) => ColorType::L8,
+                (None, _) => ColorType::Rgb8,
+            },
+        );
+
+        fn dynamic<I>(
+            (image, clipped): (I, ClippedCount),
+            f: impl Fn(I) -> DynamicImage,
+        ) -> (DynamicImage, ClippedCount) {
+            (f(image), clipped)
+        }
+
+        let luma = LumaWeights::default();
+        match color_type {
+            ColorType::L8 => {
+                dynamic(self.to_luma_image(luma), DynamicImage::ImageLuma8)
+            },
+            ColorType::La8 => {
+                dynamic(self.to_image(), DynamicImage::ImageLumaA8)
+            },
+            ColorType::Rgb8 => {
+                dynamic(self.to_image(), DynamicImage::ImageRgb8)
+            },
+            ColorType::Rgba8 => {
+                dynamic(self.to_image(), DynamicImage::ImageRgba8)
+            },
+            ColorType::L16 => {
+                dynamic(self.to_luma_image(luma), DynamicImage::ImageLuma16)
+            },
+            ColorType::La16 => {
+                dynamic(self.to_image(), DynamicImage::ImageLumaA16)
+            },
+            ColorType::Rgb16 => {
+                dynamic(self.to_image(), DynamicImage::ImageRgb16)
+            },
+            ColorType::Rgba16 => {
+                dynamic(self.to_image(), DynamicImage::ImageRgba16)
+            },
+            ColorType::Rgb32F => {
+                dynamic(self.to_image(), DynamicImage::ImageRgb32F)
+            },
+            _ => dynamic(self.to_image(), DynamicImage::ImageRgba32F),
+        }
+    }
+
+    /// Returns how the values are converted into the integer values of
+    /// images.
+    pub fn quantization(&self) -> Quantization {
+        self.quantization
+    }
+
+    /// Sets how the values are converted into the integer values of images,
+    /// by [`into_rgb`](Self::into_rgb) and the other conversions into
+    /// images with integer values.
+    pub fn with_quantization(mut self, quantization: Quantization) -> Self {
+        self.quantization = quantization;
+        self
+    }
+
+    /// Counts the values of the array outside of `0.0..=max`, which are
+    /// clipped when converted into an image with maximum value `max` (e.g.
+    /// `255.0` for 8-bit images). The values are counted as converted, i.e.
+    /// with the normalization undone and RGB colors.
+    pub fn clipped_count(&self, max: f64) -> ClippedCount {
+        ClippedCount::of(&self.to_pixel_values(), max)
+    }
+
+    /// Returns the color space of the colors of the image.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Converts the colors of the image into the given [`ColorSpace`], from
+    /// its current color space (which is RGB unless converted before). The
+    /// alpha channel (if an [`AlphaPolicy`] is set) is left unchanged, and
+    /// the colors are converted back into RGB by the conversions into
+    /// images. The values of the colors are expected in `0.0..=255.0`, or
+    /// up to the maximum value of the color type of the image if it was
+    /// converted from an image. Returns an
+    /// error if the image does not have 3 colors.
+    pub fn to_color_space(
+        &self,
+        color_space: ColorSpace,
+    ) -> Result<Self, ShapeError> {
+        if self.color_count() != 3 {
+            let incompatible = ndarray::ErrorKind::IncompatibleShape;
+            return Err(ShapeError::from_kind(incompatible));
+        }
+
+        // same memory layout as the image
+        let mut inner = self.inner.clone();
+        // the colors are converted with the values of the pixels
+        if let Some(transform) = self.normalization {
+            self.map_colors_inplace(&mut inner, |x| transform.denormalize(x));
+        }
+        convert(
+            inner.slice_mut(s![.., .., ..3]),
+            self.color_space,
+            color_space,
+            self.max_value(),
+        );
+        if let Some(transform) = self.normalization {
+            self.map_colors_inplace(&mut inner, |x| transform.normalize(x));
+        }
+        Ok(Self {
+            color_space,
+            ..self.with_inner(inner)
+        })
+    }
+
+    /// Returns the transform of the values of the colors, if they were
+    /// normalized.
+    pub fn normalization(&self) -> Option<NormalizationTransform> {
+        self.normalization
+    }
+
+    /// Normalizes the values of the colors with the given [`Normalization`],
+    /// e.g. into `0.0..=1.0` with [`Normalization::UnitRange`], so that the
+    /// same `lambda` gives the same output for images of any bit depth,
+    /// recording the transform, see [`normalization`](Self::normalization).
+    /// If the colors are already normalized, the previous normalization is
+    /// undone first. The alpha channel (if an [`AlphaPolicy`] is set) is
+    /// left unchanged. The normalization is undone by the conversions into
+    /// images, and by [`to_denormalized`](Self::to_denormalized).
+    ///
+    /// Without normalization, the values are the values of the pixels, e.g.
+    /// in `0.0..=65535.0` for 16-bit images. The solvers scale with the
+    /// values, i.e. denoising values divided by `scale` with `lambda *
+    /// scale` gives the same output divided by `scale`, so `lambda` must be
+    /// divided by 257 from 8-bit to 16-bit images to get the same output.
+    ///
+    /// Returns an error for [`Normalization::UnitRange`] if the image has no
+    /// [`color_type`](Self::color_type), i.e. if the range of its values is
+    /// not known.
+    pub fn to_normalized(
+        &self,
+        normalization: Normalization,
+    ) -> Result<Self, ShapeError> {
+        if normalization == Normalization::UnitRange
+            && self.color_type.is_none()
+        {
+            let unsupported = ndarray::ErrorKind::Unsupported;
+            return Err(ShapeError::from_kind(unsupported));
+        }
+
+        let mut denormalized = self.to_denormalized();
+        let transform = NormalizationTransform::of(
+            normalization,
+            denormalized.slice(s![.., .., ..self.color_count()]),
+            self.max_value(),
+        );
+        self.map_colors_inplace(&mut denormalized.inner, |x| {
+            transform.normalize(x)
+        });
+        denormalized.normalization = Some(transform);
+
+        Ok(denormalized)
+    }
+
+    /// Undoes the normalization of the values of the colors, if they were
+    /// normalized with [`to_normalized`](Self::to_normalized).
+    pub fn to_denormalized(&self) -> Self {
+        let mut inner = self.inner.clone();
+        if let Some(transform) = self.normalization {
+            self.map_colors_inplace(&mut inner, |x| transform.denormalize(x));
+        }
+
+        Self {
+            normalization: None,
+            ..self.with_inner(inner)
+        }
+    }
+
+    /// Applies the function to the values of the colors (i.e. all the
+    /// channels except for alpha) of an array of the shape of the image.
+    fn map_colors_inplace(
+        &self,
+        array: &mut Array3<f64>,
+        f: impl Fn(f64) -> f64,
+    ) {
+        array
+            .slice_mut(s![.., .., ..self.color_count()])
+            .mapv_inplace(f);
+    }
+
+    /// The image with the values of the pixels and RGB colors, i.e. with the
+    /// normalization undone and converted from its color space if needed.
+    pub(crate) fn to_pixel_values(&self) -> Cow<'_, Self> {
+        if self.normalization.is_none() && self.color_space == ColorSpace::Rgb {
+            return Cow::Borrowed(self);
+        }
+
+        let denormalized = self.to_denormalized();
+        Cow::Owned(match self.color_space {
+            ColorSpace::Rgb => denormalized,
+            _ => denormalized
+                .to_color_space(ColorSpace::Rgb)
+                .expect("images in other color spaces have 3 colors"),
+        })
+    }
+
+    /// Converts the array into an image of pixel type `P`, where the colors
+    /// of each pixel cycle through the color channels of the array, and the
+    /// alpha channel (if `P` has one) is the last channel of the array if an
+    /// [`AlphaPolicy`] is set, or the maximum value of `P` otherwise. An
+    /// array without colors is converted into a black image. Returns the
+    /// image and the number of values clipped to the range of `P`.
+    fn to_image<P>(&self) -> (ImageBuffer<P, Vec<P::Subpixel>>, ClippedCount)
+    where
+        P: PixelWithColorType,
+        P::Subpixel: Subpixel,
+    {
+        let rgb = self.to_pixel_values();
+        let (width, height, len_z) = rgb.dim();
+        let colors = self.color_count();
+        let has_alpha = P::COLOR_TYPE.has_alpha();
+        let color_count = P::CHANNEL_COUNT as usize - has_alpha as usize;
+
+        let max = <P::Subpixel as Primitive>::DEFAULT_MAX_VALUE;
+        let mut channels = Array3::<f64>::from_elem(
+            (width, height, P::CHANNEL_COUNT as usize).f(),
+            max.into(),
+        );
+        for z in 0..color_count {
+            let mut channel = channels.index_axis_mut(Axis(2), z);
+            match colors {
+                0 => channel.fill(0.0),
+                _ => channel.assign(&rgb.index_axis(Axis(2), z % colors)),
             }
         }
-        img
+        if has_alpha && colors < len_z {
+            channels
+                .index_axis_mut(Axis(2), color_count)
+                .assign(&rgb.index_axis(Axis(2), colors));
+        }
+
+        self.quantized_image(channels)
+    }
+
+    /// Converts the array into a gray image of pixel type `P`, see
+    /// [`into_luma_with_weights`](Self::into_luma_with_weights). Returns the
+    /// image and the number of values clipped to the range of `P`.
+    fn to_luma_image<P>(
+        &self,
+        weights: LumaWeights,
+    ) -> (ImageBuffer<P, Vec<P::Subpixel>>, ClippedCount)
+    where
+        P: Pixel,
+        P::Subpixel: Subpixel,
+    {
+        let rgb = self.to_pixel_values();
+        let luma = rgb
+            .map_axis(Axis(2), |v| self.luma_of(v, weights))
+            .insert_axis(Axis(2));
+
+        self.quantized_image(luma)
+    }
+
+    /// Creates an image of pixel type `P` from its channels (axis 2), which
+    /// are quantized with the [`Quantization`] of `self` if `P` has integer
+    /// values. Returns the image and the number of values clipped by the
+    /// quantization, which is zero if `P` has float values.
+    fn quantized_image<P>(
+        &self,
+        mut channels: Array3<f64>,
+    ) -> (ImageBuffer<P, Vec<P::Subpixel>>, ClippedCount)
+    where
+        P: Pixel,
+        P::Subpixel: Subpixel,
+    {
+        let clipped = match <P::Subpixel as Subpixel>::QUANTIZATION_MAX {
+            Some(max) => quantize(&mut channels, self.quantization, max),
+            None => ClippedCount::default(),
+        };
+
+        let (width, height, _) = channels.dim();
+        let image =
+            ImageBuffer::from_fn(width as u32, height as u32, |x, y| {
+                let mut pixel = [<P::Subpixel as Primitive>::DEFAULT_MIN_VALUE;
+                    MAX_CHANNEL_COUNT];
+                let values = channels.slice(s![x as usize, y as usize, ..]);
+                for (subpixel, &value) in pixel.iter_mut().zip(values) {
+                    *subpixel = Subpixel::from_f64(value);
+                }
+                *P::from_slice(&pixel[..P::CHANNEL_COUNT as usize])
+            });
+
+        (image, clipped)
+    }
+
+    /// The given channels (axis 2) of the image, with the same settings as
+    /// `self`, without an alpha policy. The lambda map and edge weights are
+    /// sliced too, if they have the same number of channels as `self`.
+    pub(crate) fn channels(&self, channels: Range<usize>) -> Self {
+        let len_z = self.len_of(Axis(2));
+        let slice_channels = |array: &Array3<f64>| {
+            if array.len_of(Axis(2)) == len_z {
+                array.slice(s![.., .., channels.clone()]).to_owned()
+            } else {
+                array.clone()
+            }
+        };
+
+        Self {
+            inner: slice_channels(&self.inner),
+            options: self
+                .options
+                .map_arrays(slice_channels)
+                .without_alpha_policy(),
+            color_type: None,
+            quantization: self.quantization,
+            color_space: self.color_space,
+            normalization: self.normalization,
+        }
+    }
+
+    /// Same as [`into_luma`](Self::into_luma), for 16-bit images, i.e. the
+    /// array values are expected in `0.0..=65535.0`, as when converted from
+    /// a 16-bit image.
+    pub fn into_luma16(&self) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+        self.into_luma16_with_weights(LumaWeights::default())
+    }
+
+    /// Same as [`into_luma_with_weights`](Self::into_luma_with_weights), for
+    /// 16-bit images.
+    pub fn into_luma16_with_weights(
+        &self,
+        weights: LumaWeights,
+    ) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+        self.to_luma_image(weights).0
+    }
+
+    /// Number of color channels of the image, i.e. all the channels except
+    /// for the last one if an [`AlphaPolicy`] is set.
+    fn color_count(&self) -> usize {
+        let len_z = self.len_of(Axis(2));
+        match self.alpha_policy() {
+            Some(_) if len_z > 1 => len_z - 1,
+            _ => len_z,
+        }
+    }
+
+    /// Gray value of the colors of a pixel with the given weights. The
+    /// Rec.601 and Rec.709 weights are applied to the colors as used by
+    /// [`into_rgb`](Self::into_rgb), i.e. cycling through the colors if
+    /// there are less than 3. The color of a gray pixel is returned as is,
+    /// since weighting its copies may not give back the exact value.
+    fn luma_of(&self, pixel: ArrayView1<'_, f64>, weights: LumaWeights) -> f64 {
+        let colors = pixel.slice(s![..self.color_count()]);
+        if colors.len() == 1 {
+            return colors[0];
+        }
+        let rgb_weights = match weights {
+            LumaWeights::Rec601 => [0.299, 0.587, 0.114],
+            LumaWeights::Rec709 => [0.2126, 0.7152, 0.0722],
+            LumaWeights::Mean => return colors.mean().unwrap_or(0.0),
+            LumaWeights::SaturatingSum => return colors.sum(),
+        };
+        rgb_weights
+            .iter()
+            .zip(colors.iter().cycle())
+            .map(|(weight, color)| weight * color)
+            .sum()
     }
+
+    /// Same as [`into_rgb`](Self::into_rgb), for 16-bit images, i.e. the
+    /// array values are expected in `0.0..=65535.0`, as when converted from
+    /// a 16-bit image.
+    pub fn into_rgb16(&self) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+        self.to_image().0
+    }
+
+    /// Same as [`into_rgb`](Self::into_rgb), with 32-bit float values which
+    /// are neither quantized nor clamped, e.g. for saving the image as
+    /// OpenEXR with the `image` crate. See also
+    /// [`save_pfm`](Self::save_pfm).
+    pub fn into_rgb32f(&self) -> Rgb32FImage {
+        self.to_image().0
+    }
+}
+
+/// Maximum number of channels of the pixel types of the `image` crate.
+const MAX_CHANNEL_COUNT: usize = 4;
+
+/// Copies the pixels of the image into an array of shape `(width, height,
+/// channels)`, stored with the [`Layout::Planar`] memory layout.
+fn array_from_image<P>(image: &ImageBuffer<P, Vec<P::Subpixel>>) -> Array3<f64>
+where
+    P: Pixel,
+    P::Subpixel: Into<f64>,
+{
+    let dim = (
+        image.width() as usize,
+        image.height() as usize,
+        P::CHANNEL_COUNT as usize,
+    );
+    let mut array = Array3::<f64>::zeros(dim.f());
+    for (x, y, pixel) in image.enumerate_pixels() {
+        for (z, &value) in pixel.channels().iter().enumerate() {
+            array[[x as usize, y as usize, z]] = value.into();
+        }
+    }
+    array
 }
 
 #[cfg(test)]
 mod test {
     use image::{
+        ColorType,
+        DynamicImage,
         GrayImage,
+        ImageBuffer,
         Luma,
+        LumaA,
         Rgb,
         RgbImage,
+        Rgba,
+        RgbaImage,
+    };
+    use ndarray::{
+        s,
+        Array3,
+        ShapeError,
     };
-    use ndarray::Array3;
     use pretty_assertions::assert_eq;
 
-    use super::ImageArray;
+    use super::{
+        ImageArray,
+        Layout,
+        LumaWeights,
+    };
+    use crate::{
+        color_space::ColorSpace,
+        normalization::Normalization,
+        ops::Boundary,
+        options::{
+            AlphaPolicy,
+            SolverOptions,
+            TotalVariation,
+        },
+        quantization::{
+            ClippedCount,
+            Quantization,
+        },
+    };
 
     fn make_random_gray_image(shape: (u32, u32)) -> GrayImage {
         let mut img = GrayImage::new(shape.0, shape.1);
@@ -150,6 +1028,28 @@ mod test {
         img
     }
 
+    fn make_random_rgba_image(shape: (u32, u32)) -> RgbaImage {
+        ImageBuffer::from_fn(shape.0, shape.1, |_, _| {
+            Rgba(rand::random::<[u8; 4]>())
+        })
+    }
+
+    fn make_random_gray16_image(
+        shape: (u32, u32),
+    ) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+        ImageBuffer::from_fn(shape.0, shape.1, |_, _| {
+            Luma(rand::random::<[u16; 1]>())
+        })
+    }
+
+    fn make_random_rgb16_image(
+        shape: (u32, u32),
+    ) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+        ImageBuffer::from_fn(shape.0, shape.1, |_, _| {
+            Rgb(rand::random::<[u16; 3]>())
+        })
+    }
+
     #[test]
     fn make_image_array_from_gray_image() {
         let img = make_random_gray_image((10, 5));
@@ -210,6 +1110,186 @@ mod test {
         assert_eq!(*array, test_array);
     }
 
+    #[test]
+    fn make_image_array_from_array3_view() {
+        let mut test_array = Array3::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u16>());
+
+        let array = ImageArray::from(test_array.view());
+
+        let test_array = test_array.map(|&x| x as f64);
+
+        assert_eq!(*array, test_array);
+    }
+
+    #[test]
+    fn make_image_array_from_owned_array3_f64_without_copy() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<f64>());
+        let test_ptr = test_array.as_ptr();
+
+        let array = ImageArray::from(test_array).into_inner();
+
+        assert_eq!(array.as_ptr(), test_ptr);
+    }
+
+    #[test]
+    fn image_array_with_boundary() {
+        let array = ImageArray::from(&make_random_rgb_image((10, 5)));
+
+        assert_eq!(array.boundary(), Boundary::Neumann);
+
+        let array = array.with_boundary(Boundary::Periodic);
+
+        assert_eq!(array.boundary(), Boundary::Periodic);
+        assert_eq!(
+            array.to_layout(Layout::Interleaved).boundary(),
+            Boundary::Periodic
+        );
+    }
+
+    #[test]
+    fn image_array_with_total_variation() {
+        let array = ImageArray::from(&make_random_rgb_image((10, 5)));
+
+        assert_eq!(array.total_variation(), TotalVariation::Isotropic);
+
+        let array = array.with_total_variation(TotalVariation::Anisotropic);
+
+        assert_eq!(array.total_variation(), TotalVariation::Anisotropic);
+        assert_eq!(
+            array.to_layout(Layout::Interleaved).total_variation(),
+            TotalVariation::Anisotropic
+        );
+    }
+
+    #[test]
+    fn image_array_with_options() {
+        let options = SolverOptions::default()
+            .with_boundary(Boundary::Periodic)
+            .with_total_variation(TotalVariation::Nuclear)
+            .with_lambda_map(Array3::<f64>::ones((10, 5, 1)));
+
+        let array = ImageArray::from(&make_random_rgb_image((10, 5)))
+            .with_options(options);
+
+        assert_eq!(array.boundary(), Boundary::Periodic);
+        assert_eq!(array.total_variation(), TotalVariation::Nuclear);
+        assert_eq!(array.lambda_map(), Some(&Array3::<f64>::ones((10, 5, 1))));
+        assert!(std::ptr::eq(
+            array.lambda_map().unwrap(),
+            array.to_layout(Layout::Interleaved).lambda_map().unwrap()
+        ));
+    }
+
+    #[test]
+    fn image_array_with_guide() {
+        let array = ImageArray::from(&make_random_gray_image((10, 5)));
+        // vertical edge between x = 4 and x = 5 in all channels
+        let mut guide = Array3::<f64>::zeros((10, 5, 3));
+        guide.slice_mut(s![5.., .., ..]).fill(100.0);
+
+        let array = array.with_guide(&guide, 10.0).unwrap();
+
+        let (array_guide, edge_scale) = array.options().guide().unwrap();
+        let edge_weights = array
+            .edge_weights_of_guide(array_guide, edge_scale)
+            .unwrap();
+        let edge_weight = 1.0 / (1.0 + 3.0 * 10_f64.powi(2));
+        for ((x, _, _), &weight) in edge_weights.indexed_iter() {
+            if x == 5 {
+                assert!((weight - edge_weight).abs() < 1e-12);
+            } else {
+                assert_eq!(weight, 1.0);
+            }
+        }
+        assert_eq!(edge_weights.dim(), (10, 5, 1));
+        assert_eq!(array.edge_weights(), None);
+        assert_eq!(
+            array.to_layout(Layout::Interleaved).options().guide(),
+            Some((&guide, 10.0))
+        );
+    }
+
+    #[test]
+    fn image_array_with_guide_uses_boundary_set_afterwards() {
+        let array = ImageArray::from(&make_random_gray_image((10, 5)));
+        // the periodic boundary adds an edge between x = 9 and x = 0, which
+        // is weighted at x = 0 as the edge between x = 4 and x = 5 at x = 5
+        let mut guide = Array3::<f64>::zeros((10, 5, 1));
+        guide.slice_mut(s![5.., .., ..]).fill(100.0);
+
+        let array = array
+            .with_guide(&guide, 10.0)
+            .unwrap()
+            .with_boundary(Boundary::Periodic);
+
+        let (guide, edge_scale) = array.options().guide().unwrap();
+        let edge_weights =
+            array.edge_weights_of_guide(guide, edge_scale).unwrap();
+        assert!(edge_weights[[0, 0, 0]] < 0.5);
+    }
+
+    #[test]
+    fn image_array_with_guide_returns_error_if_edge_scale_is_not_positive() {
+        let array = ImageArray::from(&make_random_gray_image((10, 5)));
+        let guide = Array3::<f64>::zeros((10, 5, 3));
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        for edge_scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                array.clone().with_guide(&guide, edge_scale).unwrap_err(),
+                unsupported_error
+            );
+        }
+    }
+
+    #[test]
+    fn image_array_with_guide_returns_error_if_shape_is_incompatible() {
+        let array = ImageArray::from(&make_random_gray_image((10, 5)));
+        let guide = Array3::<f64>::zeros((10, 4, 3));
+
+        let incompatible_shape_error =
+            ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        assert_eq!(
+            array.with_guide(&guide, 10.0).unwrap_err(),
+            incompatible_shape_error
+        );
+    }
+
+    #[test]
+    fn image_array_from_image_is_planar() {
+        let gray = ImageArray::from(&make_random_gray_image((10, 5)));
+        let rgb = ImageArray::from(&make_random_rgb_image((10, 5)));
+
+        assert_eq!(gray.layout(), Some(Layout::Planar));
+        assert_eq!(rgb.layout(), Some(Layout::Planar));
+    }
+
+    #[test]
+    fn image_array_from_array3_keeps_layout() {
+        let mut test_array = Array3::<u8>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>());
+
+        let array = ImageArray::from(&test_array);
+
+        assert_eq!(array.layout(), Some(Layout::Interleaved));
+    }
+
+    #[test]
+    fn image_array_to_layout() {
+        let array = ImageArray::from(&make_random_rgb_image((10, 5)));
+
+        let interleaved = array.to_layout(Layout::Interleaved);
+        let planar = interleaved.to_layout(Layout::Planar);
+
+        assert_eq!(interleaved.layout(), Some(Layout::Interleaved));
+        assert_eq!(planar.layout(), Some(Layout::Planar));
+        assert_eq!(*interleaved, *array);
+        assert_eq!(*planar, *array);
+    }
+
     #[test]
     fn make_gray_image_from_array3_f64() {
         let test_img = make_random_gray_image((10, 5));
@@ -229,4 +1309,466 @@ mod test {
 
         assert_eq!(img, test_img);
     }
+
+    #[test]
+    fn make_image_array_from_rgb16_image() {
+        let img = make_random_rgb16_image((10, 5));
+
+        let array = ImageArray::from(&img);
+
+        let dim = (img.width() as usize, img.height() as usize, 3);
+        let mut test_array = Array3::<f64>::zeros(dim);
+        for x in 0..dim.0 {
+            for y in 0..dim.1 {
+                let pixel = img.get_pixel(x as u32, y as u32);
+                for z in 0..3 {
+                    test_array[[x, y, z]] = pixel[z] as f64;
+                }
+            }
+        }
+
+        assert_eq!(*array, test_array);
+        assert_eq!(array.layout(), Some(Layout::Planar));
+    }
+
+    #[test]
+    fn image_array_from_16_bit_image_is_normalized_as_16_bit() {
+        let mut gray_img = make_random_gray16_image((10, 5));
+        gray_img.put_pixel(0, 0, Luma([u16::MAX]));
+        let mut rgb_img = make_random_rgb16_image((10, 5));
+        rgb_img.put_pixel(0, 0, Rgb([u16::MAX; 3]));
+
+        let gray = ImageArray::from(&gray_img);
+        let rgb = ImageArray::from(&rgb_img);
+        let normalized_gray =
+            gray.to_normalized(Normalization::UnitRange).unwrap();
+        let normalized_rgb =
+            rgb.to_normalized(Normalization::UnitRange).unwrap();
+
+        assert_eq!(gray.color_type(), Some(ColorType::L16));
+        assert_eq!(rgb.color_type(), Some(ColorType::Rgb16));
+        assert_eq!(normalized_gray.fold(f64::MIN, |a, &b| a.max(b)), 1.0);
+        assert_eq!(normalized_rgb.fold(f64::MIN, |a, &b| a.max(b)), 1.0);
+    }
+
+    #[test]
+    fn make_gray16_image_from_array3_f64() {
+        let test_img = make_random_gray16_image((10, 5));
+
+        let array = ImageArray::from(&test_img);
+        let img = array.into_luma16();
+
+        assert_eq!(img, test_img);
+    }
+
+    #[test]
+    fn make_rgb16_image_from_array3_f64() {
+        let test_img = make_random_rgb16_image((10, 5));
+
+        let array = ImageArray::from(&test_img);
+        let img = array.into_rgb16();
+
+        assert_eq!(img, test_img);
+    }
+
+    #[test]
+    fn make_image_array_from_rgba_image() {
+        let img = make_random_rgba_image((10, 5));
+
+        let array = ImageArray::from(&img);
+
+        let dim = (img.width() as usize, img.height() as usize, 4);
+        let mut test_array = Array3::<f64>::zeros(dim);
+        for x in 0..dim.0 {
+            for y in 0..dim.1 {
+                let pixel = img.get_pixel(x as u32, y as u32);
+                for z in 0..4 {
+                    test_array[[x, y, z]] = pixel[z] as f64;
+                }
+            }
+        }
+
+        assert_eq!(*array, test_array);
+        assert_eq!(array.alpha_policy(), Some(AlphaPolicy::Untouched));
+    }
+
+    #[test]
+    fn make_rgba_image_from_array3_f64() {
+        let test_img = make_random_rgba_image((10, 5));
+
+        let array = ImageArray::from(&test_img);
+        let img = array.into_rgba();
+
+        assert_eq!(img, test_img);
+    }
+
+    #[test]
+    fn make_opaque_rgba_image_from_array3_f64_without_alpha() {
+        let test_img = make_random_rgb_image((10, 5));
+
+        let array = ImageArray::from(&test_img);
+        let img = array.into_rgba();
+
+        for (pixel, test_pixel) in img.pixels().zip(test_img.pixels()) {
+            assert_eq!(pixel.0[..3], test_pixel.0);
+            assert_eq!(pixel.0[3], u8::MAX);
+        }
+    }
+
+    fn make_random_dynamic_images(shape: (u32, u32)) -> Vec<DynamicImage> {
+        vec![
+            DynamicImage::ImageLuma8(make_random_gray_image(shape)),
+            DynamicImage::ImageLumaA8(ImageBuffer::from_fn(
+                shape.0,
+                shape.1,
+                |_, _| LumaA(rand::random::<[u8; 2]>()),
+            )),
+            DynamicImage::ImageRgb8(make_random_rgb_image(shape)),
+            DynamicImage::ImageRgba8(make_random_rgba_image(shape)),
+            DynamicImage::ImageLuma16(make_random_gray16_image(shape)),
+            DynamicImage::ImageLumaA16(ImageBuffer::from_fn(
+                shape.0,
+                shape.1,
+                |_, _| LumaA(rand::random::<[u16; 2]>()),
+            )),
+            DynamicImage::ImageRgb16(make_random_rgb16_image(shape)),
+            DynamicImage::ImageRgba16(ImageBuffer::from_fn(
+                shape.0,
+                shape.1,
+                |_, _| Rgba(rand::random::<[u16; 4]>()),
+            )),
+            DynamicImage::ImageRgb32F(ImageBuffer::from_fn(
+                shape.0,
+                shape.1,
+                |_, _| Rgb(rand::random::<[f32; 3]>()),
+            )),
+            DynamicImage::ImageRgba32F(ImageBuffer::from_fn(
+                shape.0,
+                shape.1,
+                |_, _| Rgba(rand::random::<[f32; 4]>()),
+            )),
+        ]
+    }
+
+    #[test]
+    fn make_image_array_from_dynamic_image() {
+        for img in make_random_dynamic_images((10, 5)) {
+            let array = ImageArray::from(&img);
+
+            let color_type = img.color();
+            assert_eq!(array.color_type(), Some(color_type));
+            assert_eq!(
+                array.dim(),
+                (10, 5, color_type.channel_count() as usize)
+            );
+            assert_eq!(array.alpha_policy().is_some(), color_type.has_alpha());
+            assert_eq!(array.layout(), Some(Layout::Planar));
+        }
+    }
+
+    #[test]
+    fn make_dynamic_image_from_array3_f64() {
+        for test_img in make_random_dynamic_images((10, 5)) {
+            let array = ImageArray::from(&test_img);
+            let img = array.into_dynamic();
+
+            assert_eq!(img, test_img);
+        }
+    }
+
+    #[test]
+    fn make_dynamic_image_from_array3_f64_without_color_type() {
+        let gray = ImageArray::from(&Array3::<u8>::zeros((10, 5, 1)));
+        let rgb = ImageArray::from(&Array3::<u8>::zeros((10, 5, 3)));
+        let rgba = ImageArray::from(&make_random_rgba_image((10, 5)));
+
+        assert_eq!(gray.into_dynamic().color(), ColorType::L8);
+        assert_eq!(rgb.into_dynamic().color(), ColorType::Rgb8);
+        assert_eq!(rgba.into_dynamic().color(), ColorType::Rgba8);
+    }
+
+    #[test]
+    fn make_gray_image_from_rgb_array3_f64_with_weights() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let array = ImageArray::from(&test_array);
+
+        for (weights, rgb_weights) in [
+            (LumaWeights::Rec601, [0.299, 0.587, 0.114]),
+            (LumaWeights::Rec709, [0.2126, 0.7152, 0.0722]),
+            (LumaWeights::Mean, [1.0 / 3.0; 3]),
+        ] {
+            let img = array.into_luma_with_weights(weights);
+
+            for (x, y, pixel) in img.enumerate_pixels() {
+                let rgb = test_array.slice(s![x as usize, y as usize, ..]);
+                let luma = rgb[0] * rgb_weights[0]
+                    + rgb[1] * rgb_weights[1]
+                    + rgb[2] * rgb_weights[2];
+                assert_eq!(pixel[0], luma.round() as u8);
+            }
+        }
+    }
+
+    #[test]
+    fn make_gray_image_from_white_rgb_array3_f64_is_white() {
+        let array = ImageArray::from(&Array3::from_elem((10, 5, 3), 255_u8));
+        let array16 =
+            ImageArray::from(&Array3::from_elem((10, 5, 3), 65535_u16));
+
+        assert!(array.into_luma().pixels().all(|pixel| pixel[0] == 255));
+        assert!(array16
+            .into_luma16()
+            .pixels()
+            .all(|pixel| pixel[0] == 65535));
+        assert!(array
+            .into_luma_with_weights(LumaWeights::Rec601)
+            .pixels()
+            .all(|pixel| pixel[0] == 255));
+    }
+
+    #[test]
+    fn make_gray_image_from_array3_f64_with_saturating_sum() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.slice_mut(s![.., .., 0]).fill(100.0);
+        test_array.slice_mut(s![..5, .., 1]).fill(100.0);
+        test_array.slice_mut(s![..5, .., 2]).fill(100.0);
+        let array = ImageArray::from(test_array);
+
+        let img = array.into_luma_with_weights(LumaWeights::SaturatingSum);
+
+        for (x, _, pixel) in img.enumerate_pixels() {
+            assert_eq!(pixel[0], if x < 5 { 255 } else { 100 });
+        }
+    }
+
+    #[test]
+    fn make_gray_image_from_rgba_array3_f64_ignores_alpha() {
+        let mut test_img = make_random_rgba_image((10, 5));
+        let img = ImageArray::from(&test_img)
+            .into_luma_with_weights(LumaWeights::Mean);
+
+        for pixel in test_img.pixels_mut() {
+            pixel[3] = 0;
+        }
+        let test_img = ImageArray::from(&test_img)
+            .into_luma_with_weights(LumaWeights::Mean);
+
+        assert_eq!(img, test_img);
+    }
+
+    #[test]
+    fn make_gray_image_from_gray_image_with_truncate_is_same() {
+        let test_img =
+            GrayImage::from_fn(16, 16, |x, y| Luma([(16 * y + x) as u8]));
+        let array = ImageArray::from(&test_img)
+            .with_quantization(Quantization::Truncate);
+
+        for weights in [
+            LumaWeights::Rec601,
+            LumaWeights::Rec709,
+            LumaWeights::Mean,
+            LumaWeights::SaturatingSum,
+        ] {
+            assert_eq!(array.into_luma_with_weights(weights), test_img);
+        }
+    }
+
+    #[test]
+    fn make_rgb_image_from_array3_f64_with_quantization() {
+        let array = ImageArray::from(Array3::from_elem((10, 5, 3), 100.6));
+
+        let rounded = array.into_rgb();
+        let truncated = array
+            .clone()
+            .with_quantization(Quantization::Truncate)
+            .into_rgb();
+        let dithered = array
+            .with_quantization(Quantization::Dither)
+            .to_layout(Layout::Interleaved)
+            .into_rgb();
+
+        assert!(rounded.pixels().all(|pixel| pixel.0 == [101; 3]));
+        assert!(truncated.pixels().all(|pixel| pixel.0 == [100; 3]));
+        assert!(dithered.pixels().any(|pixel| pixel.0 == [100; 3]));
+        assert!(dithered.pixels().any(|pixel| pixel.0 == [101; 3]));
+    }
+
+    #[test]
+    fn image_array_clipped_count() {
+        let mut test_array = Array3::<f64>::from_elem((10, 5, 3), 100.0);
+        test_array[[0, 0, 0]] = -1.0;
+        test_array[[1, 0, 2]] = 255.5;
+        test_array[[2, 0, 1]] = 300.0;
+        let array = ImageArray::from(test_array);
+
+        assert_eq!(
+            array.clipped_count(255.0),
+            ClippedCount { below: 1, above: 2 }
+        );
+        assert_eq!(array.clipped_count(65535.0).total(), 1);
+    }
+
+    #[test]
+    fn make_rgb_image_with_report_returns_clipped_count() {
+        let mut test_array = Array3::<f64>::from_elem((10, 5, 3), 100.0);
+        test_array[[0, 0, 0]] = -1.0;
+        test_array[[1, 0, 2]] = 255.5;
+        test_array[[2, 0, 1]] = 300.0;
+        let array = ImageArray::from(test_array)
+            .with_quantization(Quantization::ClampAndReport);
+
+        let (img, clipped) = array.into_rgb_with_report();
+        let (dynamic, dynamic_clipped) = array.into_dynamic_with_report();
+
+        assert_eq!(img, array.into_rgb());
+        assert_eq!(clipped, ClippedCount { below: 1, above: 2 });
+        assert_eq!(dynamic, DynamicImage::ImageRgb8(img));
+        assert_eq!(dynamic_clipped, clipped);
+    }
+
+    #[test]
+    fn make_image_from_array3_f64_without_channels_is_black() {
+        let array = ImageArray::from(Array3::<f64>::zeros((10, 5, 0)));
+
+        let rgb = array.into_rgb();
+        let rgba = array.into_rgba();
+        let luma = array.into_luma();
+
+        assert!(rgb.pixels().all(|pixel| pixel.0 == [0, 0, 0]));
+        assert!(rgba.pixels().all(|pixel| pixel.0 == [0, 0, 0, 255]));
+        assert!(luma.pixels().all(|pixel| pixel.0 == [0]));
+    }
+
+    #[test]
+    fn make_rgb32f_image_from_array3_f64() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| (rand::random::<f32>() * 1000.0) as f64);
+        let array = ImageArray::from(&test_array);
+
+        let img = array.into_rgb32f();
+
+        for (x, y, pixel) in img.enumerate_pixels() {
+            for z in 0..3 {
+                let value = test_array[[x as usize, y as usize, z]];
+                assert_eq!(pixel[z] as f64, value);
+            }
+        }
+    }
+
+    #[test]
+    fn image_array_to_color_space() {
+        let test_img = make_random_rgba_image((10, 5));
+        let array = ImageArray::from(&test_img).to_layout(Layout::Interleaved);
+
+        for color_space in
+            [ColorSpace::YCbCr, ColorSpace::Lab, ColorSpace::Opponent]
+        {
+            let converted = array.to_color_space(color_space).unwrap();
+            let rgb = converted.to_color_space(ColorSpace::Rgb).unwrap();
+
+            assert_eq!(converted.color_space(), color_space);
+            assert_eq!(converted.layout(), Some(Layout::Interleaved));
+            assert_eq!(converted.alpha_policy(), Some(AlphaPolicy::Untouched));
+            assert_eq!(
+                converted.slice(s![.., .., 3]),
+                array.slice(s![.., .., 3])
+            );
+            assert!((&*rgb - &*array).mapv(f64::abs).sum() < 1e-8);
+            // converted back into RGB by the conversions into images
+            assert_eq!(converted.into_rgba(), test_img);
+        }
+    }
+
+    #[test]
+    fn image_array_to_color_space_returns_error_if_image_is_not_rgb() {
+        let array = ImageArray::from(&make_random_gray_image((10, 5)));
+
+        let converted = array.to_color_space(ColorSpace::Lab);
+
+        let incompatible_shape_error =
+            ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        assert_eq!(converted.unwrap_err(), incompatible_shape_error);
+    }
+
+    #[test]
+    fn image_array_to_normalized() {
+        let test_img = make_random_rgba_image((10, 5));
+        let array = ImageArray::from(&test_img);
+
+        for normalization in
+            [Normalization::UnitRange, Normalization::UnitVariance]
+        {
+            let normalized = array.to_normalized(normalization).unwrap();
+            let transform = normalized.normalization().unwrap();
+            let denormalized = normalized.to_denormalized();
+
+            assert_eq!(transform.normalization, normalization);
+            assert_eq!(
+                normalized.slice(s![.., .., 3]),
+                array.slice(s![.., .., 3])
+            );
+            assert_eq!(denormalized.normalization(), None);
+            assert!((&*denormalized - &*array).mapv(f64::abs).sum() < 1e-9);
+            // undone by the conversions into images
+            assert_eq!(normalized.into_rgba(), test_img);
+            // undoing UnitVariance can round 0 and 255 slightly out of range
+            if normalization == Normalization::UnitRange {
+                assert_eq!(normalized.clipped_count(255.0).total(), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn image_array_to_normalized_unit_range_requires_color_type() {
+        let array = ImageArray::from(Array3::from_elem((10, 5, 3), 0.5));
+        let rgb = ImageArray::from(&make_random_rgb_image((10, 5)));
+
+        let converted = array.to_normalized(Normalization::UnitRange);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        assert_eq!(converted.unwrap_err(), unsupported_error);
+        assert!(array.to_normalized(Normalization::UnitVariance).is_ok());
+        assert_eq!(rgb.color_type(), Some(ColorType::Rgb8));
+        assert!(rgb.to_normalized(Normalization::UnitRange).is_ok());
+    }
+
+    #[test]
+    fn image_array_to_normalized_unit_range_is_same_for_all_bit_depths() {
+        let img = make_random_gray_image((10, 5));
+        let img16 = ImageBuffer::from_fn(10, 5, |x, y| {
+            Luma([img.get_pixel(x, y)[0] as u16 * 257])
+        });
+
+        let array = ImageArray::from(&DynamicImage::ImageLuma8(img.clone()))
+            .to_normalized(Normalization::UnitRange)
+            .unwrap();
+        let array16 = ImageArray::from(&DynamicImage::ImageLuma16(img16))
+            .to_normalized(Normalization::UnitRange)
+            .unwrap();
+        let denoised = array.denoise_fista(10.0, 100, 0.0).unwrap();
+        let denoised16 = array16.denoise_fista(10.0, 100, 0.0).unwrap();
+
+        assert!(array.iter().all(|&x| (0.0..=1.0).contains(&x)));
+        assert!((&*array16 - &*array).mapv(f64::abs).sum() < 1e-12);
+        assert!((&*denoised16 - &*denoised).mapv(f64::abs).sum() < 1e-9);
+        assert_eq!(denoised.into_dynamic().color(), ColorType::L8);
+        assert_eq!(denoised16.into_dynamic().color(), ColorType::L16);
+    }
+
+    #[test]
+    fn image_array_to_normalized_and_to_color_space() {
+        let test_img = make_random_rgb_image((10, 5));
+        let array = ImageArray::from(&test_img)
+            .to_normalized(Normalization::UnitVariance)
+            .unwrap();
+
+        let lab = array.to_color_space(ColorSpace::Lab).unwrap();
+        let renormalized = lab.to_normalized(Normalization::UnitRange).unwrap();
+
+        assert_eq!(lab.normalization(), array.normalization());
+        assert_eq!(lab.into_rgb(), test_img);
+        assert_eq!(renormalized.into_rgb(), test_img);
+    }
 }
@@ -19,6 +19,10 @@
 //!
 //! Uses the [`image` crate](https://docs.rs/image/latest/image/) for loading and saving images, and the [`ndarray` crate](https://docs.rs/ndarray/latest/ndarray/index.html) for manipulating matrices.
 //!
+//! The operators used by the solvers (gradients, norms, etc.) are public in the
+//! [`ops`] module, so they can be used to write custom solvers, or implemented
+//! for other array types.
+//!
 //! # How to use it:
 //! Declare the dependency in you Cargo.toml
 //!
@@ -97,13 +101,37 @@
 //! ---|---
 //! ![source image, noisy](https://github.com/lily-mosquitoes/image-recovery/raw/main/examples/source_images/angry_birb_noisy.png) | ![output image, denoised](https://github.com/lily-mosquitoes/image-recovery/raw/main/examples/result_images/angry_birb_denoised.png)
 
-#![feature(test)]
-extern crate test;
-
+mod color_space;
 mod image_array;
-mod ops;
+mod normalization;
+mod npy;
+pub mod ops;
+mod options;
+mod pfm;
+mod quantization;
 mod solvers;
 
+pub use color_space::ColorSpace;
 pub use image;
-pub use image_array::ImageArray;
+pub use image_array::{
+    ImageArray,
+    Layout,
+    LumaWeights,
+};
 pub use ndarray;
+pub use normalization::{
+    Normalization,
+    NormalizationTransform,
+};
+pub use npy::NpyDtype;
+pub use ops::Boundary;
+pub use options::{
+    AlphaPolicy,
+    SolverOptions,
+    TotalVariation,
+};
+pub use quantization::{
+    ClippedCount,
+    Quantization,
+};
+pub use solvers::CfaPattern;
@@ -0,0 +1,139 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Normalization of the values of the arrays, so that the parameters of the
+//! solvers do not depend on the bit depth of the images.
+use ndarray::ArrayView3;
+
+/// How the colors of an `ImageArray` are normalized, see
+/// [`to_normalized`](crate::ImageArray::to_normalized).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// The values are divided by the maximum value of the color type of the
+    /// image (e.g. 255 for 8-bit images, 65535 for 16-bit images and 1 for
+    /// float images), i.e. mapped from the range of the image into
+    /// `0.0..=1.0`. The range is only known for arrays converted from
+    /// images, see [`color_type`](crate::ImageArray::color_type).
+    UnitRange,
+    /// The mean of the values is subtracted from them, and they are divided
+    /// by their standard deviation, i.e. they have mean 0 and variance 1.
+    UnitVariance,
+}
+
+/// Affine transform of the values of the colors of a normalized
+/// `ImageArray`, i.e. `normalized = (value - offset) / scale`, see
+/// [`normalization`](crate::ImageArray::normalization).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NormalizationTransform {
+    /// The normalization the transform was computed for.
+    pub normalization: Normalization,
+    /// Value which is mapped to 0.
+    pub offset: f64,
+    /// Value which is mapped to 1, relative to `offset`.
+    pub scale: f64,
+}
+
+impl NormalizationTransform {
+    /// Computes the transform of the given normalization for the colors,
+    /// where `max` is the maximum value of the color type of the image.
+    pub(crate) fn of(
+        normalization: Normalization,
+        colors: ArrayView3<'_, f64>,
+        max: f64,
+    ) -> Self {
+        let (offset, scale) = match normalization {
+            Normalization::UnitRange => (0.0, max),
+            Normalization::UnitVariance => {
+                let mean = colors.mean().unwrap_or(0.0);
+                let std = colors.std(0.0);
+                // a flat image is only shifted
+                (mean, if std > 0.0 { std } else { 1.0 })
+            },
+        };
+
+        Self {
+            normalization,
+            offset,
+            scale,
+        }
+    }
+
+    /// Maps a value of the image to its normalized value.
+    pub fn normalize(&self, value: f64) -> f64 {
+        (value - self.offset) / self.scale
+    }
+
+    /// Maps a normalized value back to the value of the image, i.e. the
+    /// inverse of [`normalize`](Self::normalize).
+    pub fn denormalize(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::Array3;
+    use pretty_assertions::assert_eq;
+
+    use super::{
+        Normalization,
+        NormalizationTransform,
+    };
+
+    #[test]
+    fn normalization_transform_of_unit_range() {
+        let array = Array3::from_elem((4, 3, 3), 100.0);
+
+        let transform = NormalizationTransform::of(
+            Normalization::UnitRange,
+            array.view(),
+            255.0,
+        );
+
+        assert_eq!((transform.offset, transform.scale), (0.0, 255.0));
+        assert_eq!(transform.normalize(255.0), 1.0);
+    }
+
+    #[test]
+    fn normalization_transform_of_unit_variance() {
+        let mut array = Array3::<f64>::zeros((10, 5, 3));
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+
+        let transform = NormalizationTransform::of(
+            Normalization::UnitVariance,
+            array.view(),
+            255.0,
+        );
+        let normalized = array.mapv(|x| transform.normalize(x));
+        let denormalized = normalized.mapv(|x| transform.denormalize(x));
+
+        assert!(normalized.mean().unwrap().abs() < 1e-12);
+        assert!((normalized.var(0.0) - 1.0).abs() < 1e-12);
+        assert!((&denormalized - &array).mapv(f64::abs).sum() < 1e-9);
+    }
+
+    #[test]
+    fn normalization_transform_of_flat_image_is_shift() {
+        let array = Array3::from_elem((4, 3, 1), 100.0);
+
+        let transform = NormalizationTransform::of(
+            Normalization::UnitVariance,
+            array.view(),
+            255.0,
+        );
+
+        assert_eq!((transform.offset, transform.scale), (100.0, 1.0));
+    }
+}
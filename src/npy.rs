@@ -0,0 +1,648 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Reading and writing of arrays in the NumPy `.npy` format (and `.npz`
+//! archives of them, with the `npz` feature), so that the arrays can be
+//! moved from and into NumPy without quantization.
+//!
+//! NumPy stores images with the shape `(height, width, channels)` (or
+//! `(height, width)` for gray images), i.e. with the y axis first, while the
+//! arrays of an `ImageArray` have the shape `(width, height, channels)`, so
+//! the x and y axes are swapped when reading and writing.
+use std::{
+    fs::File,
+    io::{
+        self,
+        BufReader,
+        BufWriter,
+        Read,
+        Write,
+    },
+    path::Path,
+};
+
+use ndarray::{
+    Array3,
+    ShapeBuilder,
+};
+
+use crate::{
+    image_array::ImageArray,
+    quantization::quantize,
+};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+/// Alignment of the header (including the magic string and the version),
+/// as written by NumPy.
+const HEADER_ALIGNMENT: usize = 64;
+
+/// Data type of the values of a `.npy` file, see
+/// [`write_npy`](crate::ImageArray::write_npy). Any of them can be read by
+/// [`read_npy`](crate::ImageArray::read_npy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NpyDtype {
+    /// 8-bit unsigned integers (`uint8`), quantized to `0..=255`.
+    U8,
+    /// 16-bit unsigned integers (`uint16`), quantized to `0..=65535`.
+    U16,
+    /// 32-bit floats (`float32`).
+    F32,
+    /// 64-bit floats (`float64`), the values of the array as they are.
+    #[default]
+    F64,
+}
+
+impl NpyDtype {
+    /// Size of each value in bytes.
+    fn size(&self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// Little-endian type descriptor of the header.
+    fn descr(&self) -> &'static str {
+        match self {
+            Self::U8 => "|u1",
+            Self::U16 => "<u2",
+            Self::F32 => "<f4",
+            Self::F64 => "<f8",
+        }
+    }
+
+    /// Parses a type descriptor of the header, returning the data type and
+    /// whether the values are little-endian.
+    fn from_descr(descr: &str) -> Option<(Self, bool)> {
+        let (byte_order, kind) = descr.split_at_checked(1)?;
+        let little_endian = match byte_order {
+            "<" | "|" => true,
+            ">" => false,
+            "=" => cfg!(target_endian = "little"),
+            _ => return None,
+        };
+        let dtype = match kind {
+            "u1" => Self::U8,
+            "u2" => Self::U16,
+            "f4" => Self::F32,
+            "f8" => Self::F64,
+            _ => return None,
+        };
+
+        Some((dtype, little_endian))
+    }
+
+    /// Converts the bytes of a value into `f64`.
+    fn value_from_bytes(&self, bytes: &[u8], little_endian: bool) -> f64 {
+        macro_rules! from_bytes {
+            ($type:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                if little_endian {
+                    <$type>::from_le_bytes(bytes) as f64
+                } else {
+                    <$type>::from_be_bytes(bytes) as f64
+                }
+            }};
+        }
+
+        match self {
+            Self::U8 => bytes[0] as f64,
+            Self::U16 => from_bytes!(u16),
+            Self::F32 => from_bytes!(f32),
+            Self::F64 => from_bytes!(f64),
+        }
+    }
+
+    /// Writes the (already quantized) value as little-endian bytes.
+    fn write_value<W: Write>(
+        &self,
+        writer: &mut W,
+        value: f64,
+    ) -> io::Result<()> {
+        match self {
+            Self::U8 => writer.write_all(&[value as u8]),
+            Self::U16 => writer.write_all(&(value as u16).to_le_bytes()),
+            Self::F32 => writer.write_all(&(value as f32).to_le_bytes()),
+            Self::F64 => writer.write_all(&value.to_le_bytes()),
+        }
+    }
+
+    /// Maximum value of the integer types, which are quantized.
+    fn quantization_max(&self) -> Option<f64> {
+        match self {
+            Self::U8 => Some(u8::MAX as f64),
+            Self::U16 => Some(u16::MAX as f64),
+            Self::F32 | Self::F64 => None,
+        }
+    }
+}
+
+impl ImageArray<Array3<f64>> {
+    /// Writes the array in the `.npy` format (version 1.0), with the shape
+    /// `(height, width, channels)` in C order, which is how NumPy stores
+    /// images. The values of the pixels are written, i.e. the
+    /// [normalization](crate::ImageArray::normalization) is undone and the
+    /// colors are converted into RGB, as for the conversions into images.
+    /// They are quantized with the [`Quantization`](crate::Quantization) of
+    /// the `ImageArray` for the integer data types, and converted as they
+    /// are otherwise (i.e. they are not scaled). Returns any error from
+    /// writing.
+    pub fn write_npy<W: Write>(
+        &self,
+        mut writer: W,
+        dtype: NpyDtype,
+    ) -> io::Result<()> {
+        let (width, height, len_z) = self.dim();
+        let shape = format!("({}, {}, {})", height, width, len_z);
+        let mut header = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+            dtype.descr(),
+            shape
+        );
+        // padded with spaces and terminated by a newline, such that the
+        // values are aligned
+        let preamble_len = MAGIC.len() + 4;
+        let header_len = (preamble_len + header.len() + 1)
+            .next_multiple_of(HEADER_ALIGNMENT)
+            - preamble_len;
+        header.extend(std::iter::repeat_n(' ', header_len - header.len() - 1));
+        header.push('\n');
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[1, 0])?;
+        writer.write_all(&(header_len as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+
+        let mut values = (**self.to_pixel_values()).to_owned();
+        if let Some(max) = dtype.quantization_max() {
+            quantize(&mut values, self.quantization(), max);
+        }
+        // C order of the NumPy shape, i.e. by rows (y), columns (x) and
+        // channels
+        for value in values.permuted_axes([1, 0, 2]).iter() {
+            dtype.write_value(&mut writer, *value)?;
+        }
+        writer.flush()
+    }
+
+    /// Reads an array in the `.npy` format (any version), with the shape
+    /// `(height, width, channels)` or `(height, width)`, in C or Fortran
+    /// order, and values of any [`NpyDtype`] of either endianness. The
+    /// values are read as they are, i.e. they are not scaled. The array is
+    /// stored with the [`Layout::Planar`](crate::Layout::Planar) memory
+    /// layout. Returns an error of kind `InvalidData` if the header is not
+    /// valid, or if the data type or the shape is not supported (including
+    /// a shape which does not fit in memory), of kind `UnexpectedEof` if
+    /// there are less values than given by the shape, or any error from
+    /// reading. Memory is only allocated for the data which is actually
+    /// read.
+    pub fn read_npy<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0_u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("not a NPY file"));
+        }
+        let header_len = match magic[MAGIC.len()] {
+            1 => {
+                let mut bytes = [0_u8; 2];
+                reader.read_exact(&mut bytes)?;
+                u16::from_le_bytes(bytes) as usize
+            },
+            2 | 3 => {
+                let mut bytes = [0_u8; 4];
+                reader.read_exact(&mut bytes)?;
+                u32::from_le_bytes(bytes) as usize
+            },
+            _ => return Err(invalid_data("unsupported NPY version")),
+        };
+        let header = read_bytes(&mut reader, header_len)?;
+        let header = String::from_utf8(header)
+            .map_err(|_| invalid_data("invalid NPY header"))?;
+
+        let (dtype, little_endian) = header_value(&header, "descr")
+            .and_then(|descr| NpyDtype::from_descr(descr.trim_matches('\'')))
+            .ok_or_else(|| invalid_data("unsupported NPY data type"))?;
+        let fortran_order = match header_value(&header, "fortran_order") {
+            Some("True") => true,
+            Some("False") => false,
+            _ => return Err(invalid_data("invalid NPY header")),
+        };
+        let shape = header_value(&header, "shape")
+            .and_then(parse_shape)
+            .ok_or_else(|| invalid_data("invalid NPY header"))?;
+        let (height, width, len_z) = match shape[..] {
+            [height, width] => (height, width, 1),
+            [height, width, len_z] => (height, width, len_z),
+            _ => return Err(invalid_data("unsupported NPY shape")),
+        };
+
+        let len = height
+            .checked_mul(width)
+            .and_then(|len| len.checked_mul(len_z))
+            .and_then(|len| len.checked_mul(dtype.size()))
+            .ok_or_else(|| invalid_data("NPY array size is too large"))?;
+        let bytes = read_bytes(&mut reader, len)?;
+        let values = bytes
+            .chunks_exact(dtype.size())
+            .map(|bytes| dtype.value_from_bytes(bytes, little_endian))
+            .collect();
+        let shape = (height, width, len_z).set_f(fortran_order);
+        let numpy_array = Array3::from_shape_vec(shape, values)
+            .map_err(|_| invalid_data("invalid NPY shape"))?;
+
+        let mut array = Array3::<f64>::zeros((width, height, len_z).f());
+        array.assign(&numpy_array.permuted_axes([1, 0, 2]));
+
+        Ok(Self::from(array))
+    }
+
+    /// Saves the array as a `.npy` file at the given path, see
+    /// [`write_npy`](Self::write_npy).
+    pub fn save_npy<P: AsRef<Path>>(
+        &self,
+        path: P,
+        dtype: NpyDtype,
+    ) -> io::Result<()> {
+        self.write_npy(BufWriter::new(File::create(path)?), dtype)
+    }
+
+    /// Loads an array from a `.npy` file at the given path, see
+    /// [`read_npy`](Self::read_npy).
+    pub fn load_npy<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_npy(BufReader::new(File::open(path)?))
+    }
+
+    /// Saves the arrays as a `.npz` archive at the given path, as
+    /// `numpy.savez` does, i.e. without compression, where each array is
+    /// stored under its name (without the `.npy` extension), see
+    /// [`write_npy`](Self::write_npy).
+    #[cfg(feature = "npz")]
+    pub fn save_npz<P: AsRef<Path>>(
+        path: P,
+        arrays: &[(&str, &Self)],
+        dtype: NpyDtype,
+    ) -> io::Result<()> {
+        let mut archive = zip::ZipWriter::new(File::create(path)?);
+        let options = zip::write::FileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (name, array) in arrays {
+            archive
+                .start_file(format!("{}.npy", name), options)
+                .map_err(zip_error)?;
+            array.write_npy(&mut archive, dtype)?;
+        }
+        archive.finish().map_err(zip_error)?;
+
+        Ok(())
+    }
+
+    /// Loads the array with the given name (without the `.npy` extension)
+    /// from a `.npz` archive at the given path, compressed or not, see
+    /// [`read_npy`](Self::read_npy). Returns an error of kind `NotFound` if
+    /// the archive has no array with the given name.
+    #[cfg(feature = "npz")]
+    pub fn load_npz<P: AsRef<Path>>(path: P, name: &str) -> io::Result<Self> {
+        let mut archive =
+            zip::ZipArchive::new(BufReader::new(File::open(path)?))
+                .map_err(zip_error)?;
+        let file = archive
+            .by_name(&format!("{}.npy", name))
+            .map_err(zip_error)?;
+
+        Self::read_npy(BufReader::new(file))
+    }
+}
+
+/// Finds the value of a key of the header, which is the string
+/// representation of a Python dictionary, e.g.
+/// `{'descr': '<f8', 'fortran_order': False, 'shape': (5, 10, 3), }`.
+fn header_value<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!("'{}'", key))? + key.len() + 2;
+    let value = header[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = if value.starts_with('(') {
+        value.find(')')? + 1
+    } else {
+        value.find([',', '}'])?
+    };
+
+    Some(value[..end].trim_end())
+}
+
+/// Parses a shape of the header, which is the string representation of a
+/// Python tuple, e.g. `(5, 10, 3)` or `(5,)`.
+fn parse_shape(shape: &str) -> Option<Vec<usize>> {
+    shape
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .split(',')
+        .map(str::trim)
+        .filter(|len| !len.is_empty())
+        .map(|len| len.parse().ok())
+        .collect()
+}
+
+/// Reads `len` bytes, where `len` comes from the header and is not trusted
+/// for allocating memory, i.e. only the bytes which are actually read are
+/// allocated. Returns an error of kind `UnexpectedEof` if there are less
+/// than `len` bytes.
+fn read_bytes<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(bytes)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Converts the errors of the `zip` crate, keeping the kind of the errors
+/// from reading and writing, and of missing arrays.
+#[cfg(feature = "npz")]
+fn zip_error(error: zip::result::ZipError) -> io::Error {
+    match error {
+        zip::result::ZipError::Io(error) => error,
+        zip::result::ZipError::FileNotFound => {
+            io::Error::new(io::ErrorKind::NotFound, "array not found in NPZ")
+        },
+        error => invalid_data(&error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use ndarray::{
+        Array2,
+        Array3,
+        ShapeBuilder,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::NpyDtype;
+    use crate::{
+        image_array::ImageArray,
+        ColorSpace,
+        Layout,
+        Normalization,
+    };
+
+    fn random_image_array(
+        shape: (usize, usize, usize),
+    ) -> ImageArray<Array3<f64>> {
+        let mut array = Array3::<f64>::zeros(shape);
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        ImageArray::from(array)
+    }
+
+    /// Bytes of a `.npy` file with the given header and values.
+    fn npy_bytes(header: &str, values: &[u8]) -> Vec<u8> {
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        bytes.extend(values);
+        bytes
+    }
+
+    #[test]
+    fn write_npy_and_read_npy() {
+        for dtype in [NpyDtype::U8, NpyDtype::U16, NpyDtype::F32, NpyDtype::F64]
+        {
+            for len_z in [1, 3, 4] {
+                let image_array = random_image_array((10, 5, len_z));
+
+                let mut bytes = Vec::new();
+                image_array.write_npy(&mut bytes, dtype).unwrap();
+                let read_image_array =
+                    ImageArray::read_npy(&bytes[..]).unwrap();
+
+                assert_eq!(*read_image_array, *image_array);
+                assert_eq!(read_image_array.layout(), Some(Layout::Planar));
+            }
+        }
+    }
+
+    #[test]
+    fn write_npy_header_and_row_order() {
+        let mut array = Array3::<f64>::zeros((3, 2, 1));
+        array[[1, 0, 0]] = 0.5;
+        let image_array = ImageArray::from(array);
+
+        let mut bytes = Vec::new();
+        image_array.write_npy(&mut bytes, NpyDtype::F64).unwrap();
+
+        let header =
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3, 1), }";
+        assert_eq!(&bytes[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        assert_eq!(&bytes[10..10 + header.len()], header.as_bytes());
+        assert_eq!(bytes[9 + header_len], b'\n');
+        assert_eq!(bytes.len(), 10 + header_len + 6 * 8);
+        // the second value of the first row
+        let value = &bytes[10 + header_len + 8..10 + header_len + 16];
+        assert_eq!(value, 0.5_f64.to_le_bytes());
+    }
+
+    #[test]
+    fn write_npy_quantizes_integer_dtypes() {
+        let array = Array3::from_shape_vec((3, 1, 1), vec![-3.0, 100.6, 300.0])
+            .unwrap();
+        let image_array = ImageArray::from(array);
+
+        let mut bytes = Vec::new();
+        image_array.write_npy(&mut bytes, NpyDtype::U8).unwrap();
+
+        assert_eq!(&bytes[bytes.len() - 3..], [0, 101, 255]);
+    }
+
+    #[test]
+    fn read_npy_fortran_order_and_2d_shape() {
+        // 2 rows and 3 columns, with the rows as the fastest axis
+        let header =
+            "{'descr': '|u1', 'fortran_order': True, 'shape': (2, 3), }\n";
+        let bytes = npy_bytes(header, &[1, 4, 2, 5, 3, 6]);
+
+        let image_array = ImageArray::read_npy(&bytes[..]).unwrap();
+
+        let numpy_array =
+            Array2::from_shape_vec((2, 3), vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0])
+                .unwrap();
+        let test_array =
+            numpy_array.reversed_axes().insert_axis(ndarray::Axis(2));
+        assert_eq!(*image_array, test_array);
+    }
+
+    #[test]
+    fn read_npy_big_endian() {
+        let header =
+            "{'descr': '>u2', 'fortran_order': False, 'shape': (1, 2, 1), }\n";
+        let bytes = npy_bytes(header, &[1, 0, 0, 1]);
+
+        let image_array = ImageArray::read_npy(&bytes[..]).unwrap();
+
+        let test_array =
+            Array3::from_shape_vec((2, 1, 1).f(), vec![256.0, 1.0]).unwrap();
+        assert_eq!(*image_array, test_array);
+    }
+
+    #[test]
+    fn write_npy_undoes_normalization() {
+        let image_array =
+            ImageArray::from(Array3::<f64>::from_elem((10, 5, 3), 200.0))
+                .to_normalized(Normalization::UnitVariance)
+                .unwrap();
+
+        for dtype in [NpyDtype::U8, NpyDtype::F64] {
+            let mut bytes = Vec::new();
+            image_array.write_npy(&mut bytes, dtype).unwrap();
+            let read_image_array = ImageArray::read_npy(&bytes[..]).unwrap();
+
+            assert!(read_image_array.iter().all(|&x| (x - 200.0).abs() < 1e-9));
+        }
+    }
+
+    #[test]
+    fn write_npy_converts_colors_into_rgb() {
+        let image_array =
+            ImageArray::from(Array3::<f64>::from_elem((10, 5, 3), 200.0))
+                .to_color_space(ColorSpace::Lab)
+                .unwrap();
+
+        for dtype in [NpyDtype::U8, NpyDtype::F64] {
+            let mut bytes = Vec::new();
+            image_array.write_npy(&mut bytes, dtype).unwrap();
+            let read_image_array = ImageArray::read_npy(&bytes[..]).unwrap();
+
+            assert!(read_image_array.iter().all(|&x| (x - 200.0).abs() < 1e-9));
+        }
+    }
+
+    #[test]
+    fn read_npy_returns_error_if_file_is_invalid() {
+        let not_npy = ImageArray::read_npy(&b"P6\n1 1\n255\n"[..]);
+        let unsupported_dtype = ImageArray::read_npy(
+            &npy_bytes(
+                "{'descr': '<i8', 'fortran_order': False, 'shape': (1, 1), }\n",
+                &[0; 8],
+            )[..],
+        );
+        let unsupported_shape = ImageArray::read_npy(
+            &npy_bytes(
+                "{'descr': '|u1', 'fortran_order': False, 'shape': (4,), }\n",
+                &[0; 4],
+            )[..],
+        );
+        let truncated = ImageArray::read_npy(
+            &npy_bytes(
+                "{'descr': '|u1', 'fortran_order': False, 'shape': (2, 2), }\n",
+                &[0; 3],
+            )[..],
+        );
+
+        for invalid in [not_npy, unsupported_dtype, unsupported_shape] {
+            assert_eq!(invalid.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+        assert_eq!(truncated.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_npy_returns_error_if_size_is_too_large() {
+        let too_large = ImageArray::read_npy(
+            &npy_bytes(
+                "{'descr': '<f8', 'fortran_order': False, 'shape': \
+                 (4294967296, 4294967296, 3), }\n",
+                &[0; 8],
+            )[..],
+        );
+        let empty_too_large = ImageArray::read_npy(
+            &npy_bytes(
+                "{'descr': '|u1', 'fortran_order': False, 'shape': (0, \
+                 18446744073709551615, 3), }\n",
+                &[],
+            )[..],
+        );
+        // a size which fits in memory, but is larger than the data
+        let truncated = ImageArray::read_npy(
+            &npy_bytes(
+                "{'descr': '<f8', 'fortran_order': False, 'shape': (100000, \
+                 100000, 3), }\n",
+                &[0; 8],
+            )[..],
+        );
+
+        assert_eq!(too_large.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            empty_too_large.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(truncated.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_npy_returns_error_if_header_is_truncated() {
+        // version 2.0 with a header length of 4 GiB, but no header
+        let mut bytes = b"\x93NUMPY\x02\x00".to_vec();
+        bytes.extend(u32::MAX.to_le_bytes());
+
+        let truncated = ImageArray::read_npy(&bytes[..]);
+
+        assert_eq!(truncated.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn save_npy_and_load_npy() {
+        let image_array = random_image_array((10, 5, 3));
+        let path = std::env::temp_dir()
+            .join(format!("image_recovery_test_{}.npy", rand::random::<u64>()));
+
+        image_array.save_npy(&path, NpyDtype::F32).unwrap();
+        let loaded_image_array = ImageArray::load_npy(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(*loaded_image_array.unwrap(), *image_array);
+    }
+
+    #[cfg(feature = "npz")]
+    #[test]
+    fn save_npz_and_load_npz() {
+        let gray = random_image_array((10, 5, 1));
+        let rgb = random_image_array((10, 5, 3));
+        let path = std::env::temp_dir()
+            .join(format!("image_recovery_test_{}.npz", rand::random::<u64>()));
+
+        ImageArray::save_npz(
+            &path,
+            &[("gray", &gray), ("rgb", &rgb)],
+            NpyDtype::U8,
+        )
+        .unwrap();
+        let loaded_gray = ImageArray::load_npz(&path, "gray");
+        let loaded_rgb = ImageArray::load_npz(&path, "rgb");
+        let missing = ImageArray::load_npz(&path, "alpha");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(*loaded_gray.unwrap(), *gray);
+        assert_eq!(*loaded_rgb.unwrap(), *rgb);
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
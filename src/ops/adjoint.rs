@@ -0,0 +1,227 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ndarray::{
+    Array,
+    Dimension,
+    ShapeError,
+};
+
+use crate::ops::Norm;
+
+/// Calculates how far an operator pair is from being adjoint, i.e. from
+/// `(K_X * Y).sum() == (X * KA_Y).sum()` for all X and Y, where K_X is the
+/// `forward` operator applied to X and KA_Y is the `adjoint` operator
+/// applied to Y. X and Y are random arrays of shapes `input_shape` and
+/// `output_shape`, with elements in `[-1, 1)`, and the check is repeated
+/// `trials` times.
+///
+/// Returns the largest difference between both sides over all trials,
+/// relative to the largest of `norm(K_X) * norm(Y)` and `norm(X) *
+/// norm(KA_Y)`, or any error returned by the operators. Returns an error of
+/// kind `Unsupported` if `trials` is 0, since nothing would be checked.
+///
+/// Requires the `adjoint` feature, which adds the `rand` dependency.
+pub fn adjoint_mismatch<D, E, F, G>(
+    forward: F,
+    adjoint: G,
+    input_shape: D,
+    output_shape: E,
+    trials: usize,
+) -> Result<f64, ShapeError>
+where
+    D: Dimension,
+    E: Dimension,
+    F: Fn(&Array<f64, D>) -> Result<Array<f64, E>, ShapeError>,
+    G: Fn(&Array<f64, E>) -> Result<Array<f64, D>, ShapeError>,
+{
+    if trials == 0 {
+        let unsupported = ndarray::ErrorKind::Unsupported;
+        return Err(ShapeError::from_kind(unsupported));
+    }
+
+    let mut mismatch: f64 = 0.0;
+    for _ in 0..trials {
+        let x = random_array(input_shape.clone());
+        let y = random_array(output_shape.clone());
+
+        let forward_x = forward(&x)?;
+        let adjoint_y = adjoint(&y)?;
+        if forward_x.raw_dim() != y.raw_dim()
+            || adjoint_y.raw_dim() != x.raw_dim()
+        {
+            let incompatible = ndarray::ErrorKind::IncompatibleShape;
+            return Err(ShapeError::from_kind(incompatible));
+        }
+
+        // both sides are bounded by these products (Cauchy-Schwarz), which
+        // unlike the sides themselves cannot be close to zero by cancellation
+        let scale =
+            (forward_x.norm() * y.norm()).max(x.norm() * adjoint_y.norm());
+        let lhs = (forward_x * &y).sum();
+        let rhs = (&x * adjoint_y).sum();
+        if scale > 0_f64 {
+            mismatch = mismatch.max((lhs - rhs).abs() / scale);
+        }
+    }
+
+    Ok(mismatch)
+}
+
+/// Array of the given shape with random elements in `[-1, 1)`.
+fn random_array<D: Dimension>(shape: D) -> Array<f64, D> {
+    let mut array = Array::<f64, D>::zeros(shape);
+    array.mapv_inplace(|_| 2_f64 * rand::random::<f64>() - 1_f64);
+    array
+}
+
+/// Checks that an operator pair is adjoint up to the relative `tolerance`,
+/// on `trials` random inputs, see [`adjoint_mismatch`]. This is useful to
+/// verify custom operators used with the solvers. Requires the `adjoint`
+/// feature.
+///
+/// ```rust
+/// use image_recovery::{
+///     ndarray::{
+///         Array3,
+///         Ix3,
+///     },
+///     ops::{
+///         is_adjoint,
+///         Boundary,
+///         Gradient,
+///     },
+/// };
+///
+/// let forward = |x: &Array3<f64>| {
+///     x.positive_gradient_on_axis_with_boundary(0, Boundary::Neumann)
+/// };
+/// let adjoint = |y: &Array3<f64>| {
+///     y.negative_gradient_on_axis_with_boundary(0, Boundary::Neumann)
+/// };
+/// let shape = Ix3(10, 5, 3);
+///
+/// assert!(is_adjoint(forward, adjoint, shape, shape, 10, 1e-12).unwrap());
+/// ```
+pub fn is_adjoint<D, E, F, G>(
+    forward: F,
+    adjoint: G,
+    input_shape: D,
+    output_shape: E,
+    trials: usize,
+    tolerance: f64,
+) -> Result<bool, ShapeError>
+where
+    D: Dimension,
+    E: Dimension,
+    F: Fn(&Array<f64, D>) -> Result<Array<f64, E>, ShapeError>,
+    G: Fn(&Array<f64, E>) -> Result<Array<f64, D>, ShapeError>,
+{
+    let mismatch =
+        adjoint_mismatch(forward, adjoint, input_shape, output_shape, trials)?;
+
+    Ok(mismatch <= tolerance)
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{
+        Array2,
+        Array3,
+        Ix2,
+        Ix3,
+        ShapeError,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{
+        adjoint_mismatch,
+        is_adjoint,
+    };
+    use crate::ops::{
+        Boundary,
+        Gradient,
+    };
+
+    #[test]
+    fn positive_and_negative_gradient_on_axis_are_adjoint() {
+        let shape = Ix3(10, 5, 3);
+
+        for boundary in
+            [Boundary::Periodic, Boundary::Neumann, Boundary::Dirichlet]
+        {
+            for axis in 0..3 {
+                let forward = |x: &Array3<f64>| {
+                    x.positive_gradient_on_axis_with_boundary(axis, boundary)
+                };
+                let adjoint = |y: &Array3<f64>| {
+                    y.negative_gradient_on_axis_with_boundary(axis, boundary)
+                };
+
+                assert!(is_adjoint(forward, adjoint, shape, shape, 10, 1e-12)
+                    .unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn positive_gradient_on_axis_is_not_self_adjoint() {
+        let shape = Ix3(10, 5, 3);
+        let forward = |x: &Array3<f64>| x.positive_gradient_on_axis(0);
+
+        let mismatch =
+            adjoint_mismatch(forward, forward, shape, shape, 10).unwrap();
+
+        assert!(mismatch > 1e-3);
+    }
+
+    #[test]
+    fn transpose_is_adjoint_for_different_shapes() {
+        let forward = |x: &Array2<f64>| Ok(x.t().to_owned());
+        let adjoint = |y: &Array2<f64>| Ok(y.t().to_owned());
+
+        let mismatch =
+            adjoint_mismatch(forward, adjoint, Ix2(4, 7), Ix2(7, 4), 10)
+                .unwrap();
+
+        assert!(mismatch < 1e-12);
+    }
+
+    #[test]
+    fn adjoint_mismatch_returns_error_if_output_shape_is_wrong() {
+        let forward = |x: &Array2<f64>| Ok(x.t().to_owned());
+        let adjoint = |y: &Array2<f64>| Ok(y.t().to_owned());
+
+        let mismatch =
+            adjoint_mismatch(forward, adjoint, Ix2(4, 7), Ix2(4, 7), 10);
+
+        let incompatible_shape_error =
+            ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        assert_eq!(mismatch, Err(incompatible_shape_error));
+    }
+
+    #[test]
+    fn is_adjoint_returns_error_for_zero_trials() {
+        let forward = |x: &Array2<f64>| Ok(x.t().to_owned());
+        let adjoint = |y: &Array2<f64>| Ok(y.t().to_owned());
+
+        let checked =
+            is_adjoint(forward, adjoint, Ix2(4, 7), Ix2(7, 4), 0, 1e-12);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        assert_eq!(checked, Err(unsupported_error));
+    }
+}
@@ -1,12 +1,68 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::ops::{
+    Add,
+    Div,
+    Mul,
+};
+
 use ndarray::{
     Array,
     Dimension,
+    ShapeError,
+    Zip,
 };
 
 /// Trait for calculating the weighted average of two arrays, given some scalars
-/// tau and lambda
-pub trait Average {
+/// tau and lambda, or a scalar tau and an array of lambdas
+pub trait Average: Sized {
+    /// Calculates the weighted average of two arrays given some scalars tau
+    /// and lambda, where `self` is weighted by `tau * lambda` and `other` by
+    /// `1`. This is the proximal operator of the data fidelity term of the
+    /// denoising problem, where `self` is the input image.
     fn weighted_average(&self, other: &Self, tau: f64, lambda: f64) -> Self;
+
+    /// Same as [`weighted_average`](Self::weighted_average), with a
+    /// different lambda for each element, i.e. each element of `self` is
+    /// weighted by `tau` times the element of `lambda` with the same index.
+    /// Must be checked for `other` having the same shape as `self`, and for
+    /// `lambda` being broadcastable to the shape of `self` (e.g. one lambda
+    /// per pixel for all the channels of an image).
+    ///
+    /// Provided using the arithmetic operators of Self, as `(other + (tau *
+    /// lambda * self)) / (1.0 + tau * lambda)`, which are relied on for
+    /// checking the shapes.
+    fn weighted_average_with_lambda_map(
+        &self,
+        other: &Self,
+        tau: f64,
+        lambda: &Self,
+    ) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Add<Output = Self>
+            + Mul<Output = Self>
+            + Div<Output = Self>
+            + Add<f64, Output = Self>
+            + Mul<f64, Output = Self>,
+    {
+        let tau_lambda = lambda * tau;
+        let numerator = other + &(&tau_lambda * self);
+
+        Ok(&numerator / &(&tau_lambda + 1.0))
+    }
 }
 
 impl<D: Dimension> Average for Array<f64, D> {
@@ -16,15 +72,102 @@ impl<D: Dimension> Average for Array<f64, D> {
     fn weighted_average(&self, other: &Self, tau: f64, lambda: f64) -> Self {
         (other + (tau * lambda * self)) / (1.0 + tau * lambda)
     }
+
+    /// Calculates the weighted average of two arrays given some scalar tau
+    /// and an array of lambdas broadcast to the shape of `self`, equivalent
+    /// to `(other + (tau * lambda * self)) / (1.0 + tau * lambda)` for each
+    /// element. The output keeps the memory layout of `other`.
+    fn weighted_average_with_lambda_map(
+        &self,
+        other: &Self,
+        tau: f64,
+        lambda: &Self,
+    ) -> Result<Self, ShapeError> {
+        let incompatible_shape =
+            || ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        if other.shape() != self.shape() {
+            return Err(incompatible_shape());
+        }
+        let lambda = lambda
+            .broadcast(self.raw_dim())
+            .ok_or_else(incompatible_shape)?;
+
+        let mut average = other.clone();
+        Zip::from(&mut average).and(self).and(&lambda).for_each(
+            |average, &value, &lambda| {
+                *average =
+                    (*average + (tau * lambda * value)) / (1.0 + tau * lambda)
+            },
+        );
+        Ok(average)
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use ndarray::Array3;
+    use ndarray::{
+        Array3,
+        ShapeError,
+    };
     use pretty_assertions::assert_eq;
 
     use super::Average;
 
+    /// Scalar implementing only the required methods of `Average`.
+    #[derive(Debug, PartialEq)]
+    struct Scalar(f64);
+
+    impl std::ops::Add for &Scalar {
+        type Output = Scalar;
+
+        fn add(self, other: Self) -> Scalar {
+            Scalar(self.0 + other.0)
+        }
+    }
+
+    impl std::ops::Mul for &Scalar {
+        type Output = Scalar;
+
+        fn mul(self, other: Self) -> Scalar {
+            Scalar(self.0 * other.0)
+        }
+    }
+
+    impl std::ops::Div for &Scalar {
+        type Output = Scalar;
+
+        fn div(self, other: Self) -> Scalar {
+            Scalar(self.0 / other.0)
+        }
+    }
+
+    impl std::ops::Add<f64> for &Scalar {
+        type Output = Scalar;
+
+        fn add(self, other: f64) -> Scalar {
+            Scalar(self.0 + other)
+        }
+    }
+
+    impl std::ops::Mul<f64> for &Scalar {
+        type Output = Scalar;
+
+        fn mul(self, other: f64) -> Scalar {
+            Scalar(self.0 * other)
+        }
+    }
+
+    impl Average for Scalar {
+        fn weighted_average(
+            &self,
+            other: &Self,
+            tau: f64,
+            lambda: f64,
+        ) -> Self {
+            Scalar((other.0 + (tau * lambda * self.0)) / (1.0 + tau * lambda))
+        }
+    }
+
     #[test]
     fn array_f64_weighted_average() {
         let mut a = Array3::zeros((10, 5, 3));
@@ -41,4 +184,69 @@ mod test {
 
         assert_eq!(average, test_average);
     }
+
+    #[test]
+    fn array_f64_weighted_average_with_lambda_map() {
+        let mut a = Array3::zeros((10, 5, 3));
+        let mut b = Array3::zeros((10, 5, 3));
+        let mut lambda = Array3::zeros((10, 5, 3));
+        a.mapv_inplace(|_| rand::random::<f64>());
+        b.mapv_inplace(|_| rand::random::<f64>());
+        lambda.mapv_inplace(|_| rand::random::<f64>());
+
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+
+        let average = a.weighted_average_with_lambda_map(&b, tau, &lambda);
+
+        let test_average = (&b + (tau * &lambda * &a)) / (1.0 + tau * &lambda);
+
+        assert_eq!(average.unwrap(), test_average);
+    }
+
+    #[test]
+    fn array_f64_weighted_average_with_constant_lambda_map_is_weighted_average()
+    {
+        let mut a = Array3::zeros((10, 5, 3));
+        let mut b = Array3::zeros((10, 5, 3));
+        a.mapv_inplace(|_| rand::random::<f64>());
+        b.mapv_inplace(|_| rand::random::<f64>());
+
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let lambda: f64 = 0.008;
+        // one lambda per pixel, broadcast to all the channels
+        let lambda_map = Array3::from_elem((10, 5, 1), lambda);
+
+        let average = a.weighted_average_with_lambda_map(&b, tau, &lambda_map);
+
+        let test_average = a.weighted_average(&b, tau, lambda);
+
+        assert!((average.unwrap() - test_average).mapv(f64::abs).sum() < 1e-12);
+    }
+
+    #[test]
+    fn provided_weighted_average_with_lambda_map_is_weighted_average() {
+        let a = Scalar(0.25);
+        let b = Scalar(0.75);
+
+        let average = a.weighted_average_with_lambda_map(&b, 0.5, &Scalar(2.0));
+
+        assert_eq!(average.unwrap(), a.weighted_average(&b, 0.5, 2.0));
+    }
+
+    #[test]
+    fn array_f64_weighted_average_with_lambda_map_returns_error_if_shape_is_incompatible(
+    ) {
+        let a = Array3::zeros((10, 5, 3));
+        let b = Array3::zeros((10, 5, 3));
+        let lambda = Array3::zeros((10, 4, 1));
+
+        let average = a.weighted_average_with_lambda_map(&b, 1.0, &lambda);
+        let other_average =
+            a.weighted_average_with_lambda_map(&lambda, 1.0, &b);
+
+        let incompatible_shape_error =
+            ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        assert_eq!(average, Err(incompatible_shape_error.clone()));
+        assert_eq!(other_average, Err(incompatible_shape_error));
+    }
 }
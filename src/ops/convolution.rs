@@ -0,0 +1,509 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ndarray::{
+    s,
+    Array2,
+    Array3,
+    ArrayView2,
+    Axis,
+    Dim,
+    ShapeError,
+};
+use rustfft::{
+    num_complex::Complex,
+    FftDirection,
+    FftPlanner,
+};
+
+use crate::ops::{
+    gradient::zeros_with_layout_of,
+    Boundary,
+};
+
+/// Kernels with at most this many elements are convolved directly, larger
+/// kernels are convolved using the FFT.
+const MAX_DIRECT_KERNEL_LEN: usize = 49;
+
+/// Trait for calculating the convolution of an image with a 2 dimentional
+/// kernel, on the x and y axes (axes 0 and 1), for each channel (axis 2)
+/// independently. The convolution must be implemented such that for all A
+/// and B, (C_A * B).sum() == (A * CA_B).sum(), where A and B are arrays of
+/// the same shape, C_A is the convolution of A and CA_B is the adjoint
+/// convolution of B, with the same kernel and [`Boundary`].
+pub trait Convolution: Sized {
+    /// Must output a same shape array, where each element is the sum of the
+    /// elements around it weighted by the kernel, i.e. `out[x, y] =
+    /// sum(kernel[i, j] * self[x - i + ci, y - j + cj])`, where `(ci, cj)`
+    /// is the center of the kernel, `((len_i - 1) / 2, (len_j - 1) / 2)`.
+    /// The elements outside of the array are given by the [`Boundary`], with
+    /// the same semantics as the shifts of [`Gradient`](crate::ops::Gradient),
+    /// such that the kernel `[[1.0], [-1.0]]` is the positive gradient on
+    /// axis 0. Must be checked for the kernel and the x and y axes not being
+    /// empty.
+    fn convolve(
+        &self,
+        kernel: &Array2<f64>,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>;
+
+    /// Must output the adjoint of the convolution with the same kernel and
+    /// [`Boundary`]. Must be checked for the kernel and the x and y axes not
+    /// being empty.
+    fn convolve_adjoint(
+        &self,
+        kernel: &Array2<f64>,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>;
+}
+
+impl Convolution for Array3<f64> {
+    /// Outputs a same shape array with the convolution of each channel with
+    /// the kernel. The array is padded according to the [`Boundary`], and
+    /// convolved directly for kernels with up to 49 elements (e.g. 7x7), or
+    /// using the FFT for larger kernels. The output keeps the memory layout
+    /// of the input.
+    fn convolve(
+        &self,
+        kernel: &Array2<f64>,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError> {
+        check_convolution(self, kernel)?;
+
+        let (len_x, len_y, _) = self.dim();
+        let flipped = kernel.slice(s![..;-1, ..;-1]);
+        let padded = pad(self, kernel.dim(), boundary);
+        let convolved = if kernel.len() <= MAX_DIRECT_KERNEL_LEN {
+            correlate_valid_direct(&padded, flipped, (len_x, len_y))
+        } else {
+            correlate_valid_fft(&padded, flipped, (len_x, len_y))
+        };
+
+        Ok(convolved)
+    }
+
+    /// Outputs a same shape array with the adjoint of the convolution of
+    /// each channel with the kernel, which is the full convolution with the
+    /// flipped kernel, folded back into the shape of the array according to
+    /// the [`Boundary`]. Computed directly for kernels with up to 49 elements
+    /// (e.g. 7x7), or using the FFT for larger kernels. The output keeps the
+    /// memory layout of the input.
+    fn convolve_adjoint(
+        &self,
+        kernel: &Array2<f64>,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError> {
+        check_convolution(self, kernel)?;
+
+        let (len_x, len_y, _) = self.dim();
+        let flipped = kernel.slice(s![..;-1, ..;-1]);
+        let padded = if kernel.len() <= MAX_DIRECT_KERNEL_LEN {
+            convolve_full_direct(self, flipped)
+        } else {
+            convolve_full_fft(self, flipped)
+        };
+
+        Ok(fold(&padded, (len_x, len_y), kernel.dim(), boundary))
+    }
+}
+
+/// Checks that neither the kernel nor the x and y axes of the array are
+/// empty, as required by the convolution.
+fn check_convolution(
+    array: &Array3<f64>,
+    kernel: &Array2<f64>,
+) -> Result<(), ShapeError> {
+    if kernel.is_empty()
+        || array.len_of(Axis(0)) < 1
+        || array.len_of(Axis(1)) < 1
+    {
+        let unsupported = ndarray::ErrorKind::Unsupported;
+        return Err(ShapeError::from_kind(unsupported));
+    }
+
+    Ok(())
+}
+
+/// Padding (before, after) needed on an axis for a kernel of the given len.
+fn padding(kernel_len: usize) -> (usize, usize) {
+    let center = (kernel_len - 1) / 2;
+    (kernel_len - 1 - center, center)
+}
+
+/// Index inside an axis of the given len of the element at `index`, which
+/// may be outside of the axis, according to the [`Boundary`]. Returns `None`
+/// if the element is zero.
+fn extended_index(
+    index: isize,
+    len: usize,
+    boundary: Boundary,
+) -> Option<usize> {
+    let len = len as isize;
+    let index = match boundary {
+        Boundary::Periodic => index.rem_euclid(len),
+        Boundary::Neumann => index.clamp(0, len - 1),
+        Boundary::Dirichlet => (0..len).contains(&index).then_some(index)?,
+    };
+
+    Some(index as usize)
+}
+
+/// Extends the array on the given axis by `before` and `after` elements,
+/// according to the [`Boundary`].
+fn extend_axis(
+    array: &Array3<f64>,
+    axis: usize,
+    (before, after): (usize, usize),
+    boundary: Boundary,
+) -> Array3<f64> {
+    let len = array.len_of(Axis(axis));
+    let mut dim = array.raw_dim();
+    dim[axis] = before + len + after;
+    let mut extended = zeros_with_layout_of(array, dim);
+    for index in 0..dim[axis] {
+        let source = index as isize - before as isize;
+        if let Some(source) = extended_index(source, len, boundary) {
+            extended
+                .index_axis_mut(Axis(axis), index)
+                .assign(&array.index_axis(Axis(axis), source));
+        }
+    }
+    extended
+}
+
+/// Adjoint of [`extend_axis`], adds the extended elements back to the
+/// elements of the axis of the given len they were copied from.
+fn fold_axis(
+    extended: &Array3<f64>,
+    axis: usize,
+    len: usize,
+    before: usize,
+    boundary: Boundary,
+) -> Array3<f64> {
+    let mut dim = extended.raw_dim();
+    dim[axis] = len;
+    let mut folded = zeros_with_layout_of(extended, dim);
+    for index in 0..extended.len_of(Axis(axis)) {
+        let source = index as isize - before as isize;
+        if let Some(source) = extended_index(source, len, boundary) {
+            let mut lane = folded.index_axis_mut(Axis(axis), source);
+            lane += &extended.index_axis(Axis(axis), index);
+        }
+    }
+    folded
+}
+
+/// Pads the array on the x and y axes for a convolution with a kernel of the
+/// given shape, according to the [`Boundary`].
+fn pad(
+    array: &Array3<f64>,
+    (kernel_x, kernel_y): (usize, usize),
+    boundary: Boundary,
+) -> Array3<f64> {
+    let extended = extend_axis(array, 0, padding(kernel_x), boundary);
+    extend_axis(&extended, 1, padding(kernel_y), boundary)
+}
+
+/// Adjoint of [`pad`], folds the padded array back into the given shape on
+/// the x and y axes.
+fn fold(
+    padded: &Array3<f64>,
+    (len_x, len_y): (usize, usize),
+    (kernel_x, kernel_y): (usize, usize),
+    boundary: Boundary,
+) -> Array3<f64> {
+    let folded = fold_axis(padded, 1, len_y, padding(kernel_y).0, boundary);
+    fold_axis(&folded, 0, len_x, padding(kernel_x).0, boundary)
+}
+
+/// Correlation of each channel of the padded array with the kernel, only
+/// where the kernel fits inside of it, i.e. `out[x, y] = sum(kernel[i, j] *
+/// padded[x + i, y + j])` for the given output shape.
+fn correlate_valid_direct(
+    padded: &Array3<f64>,
+    kernel: ArrayView2<f64>,
+    (len_x, len_y): (usize, usize),
+) -> Array3<f64> {
+    let dim = Dim([len_x, len_y, padded.len_of(Axis(2))]);
+    let mut correlated = zeros_with_layout_of(padded, dim);
+    for ((i, j), &weight) in kernel.indexed_iter() {
+        let window = padded.slice(s![i..i + len_x, j..j + len_y, ..]);
+        correlated.scaled_add(weight, &window);
+    }
+    correlated
+}
+
+/// Adjoint of [`correlate_valid_direct`], i.e. the full convolution of each
+/// channel of the array with the kernel.
+fn convolve_full_direct(
+    array: &Array3<f64>,
+    kernel: ArrayView2<f64>,
+) -> Array3<f64> {
+    let (len_x, len_y, len_z) = array.dim();
+    let (kernel_x, kernel_y) = kernel.dim();
+    let dim = Dim([len_x + kernel_x - 1, len_y + kernel_y - 1, len_z]);
+    let mut convolved = zeros_with_layout_of(array, dim);
+    for ((i, j), &weight) in kernel.indexed_iter() {
+        let mut window =
+            convolved.slice_mut(s![i..i + len_x, j..j + len_y, ..]);
+        window.scaled_add(weight, array);
+    }
+    convolved
+}
+
+/// Same as [`correlate_valid_direct`], using the FFT. The circular
+/// correlation on the shape of the padded array does not wrap around for
+/// the elements of the output.
+fn correlate_valid_fft(
+    padded: &Array3<f64>,
+    kernel: ArrayView2<f64>,
+    (len_x, len_y): (usize, usize),
+) -> Array3<f64> {
+    let dim = Dim([len_x, len_y, padded.len_of(Axis(2))]);
+    let mut correlated = zeros_with_layout_of(padded, dim);
+    correlated.assign(&circular_fft(padded, kernel, true).slice(s![
+        ..len_x,
+        ..len_y,
+        ..
+    ]));
+    correlated
+}
+
+/// Same as [`convolve_full_direct`], using the FFT. The array is padded with
+/// zeros to the shape of the output, so that the circular convolution does
+/// not wrap around.
+fn convolve_full_fft(
+    array: &Array3<f64>,
+    kernel: ArrayView2<f64>,
+) -> Array3<f64> {
+    let (len_x, len_y, len_z) = array.dim();
+    let (kernel_x, kernel_y) = kernel.dim();
+    let dim = Dim([len_x + kernel_x - 1, len_y + kernel_y - 1, len_z]);
+    let mut padded = zeros_with_layout_of(array, dim);
+    padded.slice_mut(s![..len_x, ..len_y, ..]).assign(array);
+
+    circular_fft(&padded, kernel, false)
+}
+
+/// Circular correlation (or convolution if not `correlate`) of each channel
+/// of the array with the kernel on the x and y axes, using the FFT.
+fn circular_fft(
+    array: &Array3<f64>,
+    kernel: ArrayView2<f64>,
+    correlate: bool,
+) -> Array3<f64> {
+    let (len_x, len_y, len_z) = array.dim();
+    let mut planner = FftPlanner::<f64>::new();
+
+    let mut kernel_fft = Array2::<Complex<f64>>::zeros((len_x, len_y));
+    kernel_fft
+        .slice_mut(s![..kernel.len_of(Axis(0)), ..kernel.len_of(Axis(1))])
+        .assign(&kernel.map(|&x| Complex::new(x, 0_f64)));
+    fft2(&mut kernel_fft, &mut planner, FftDirection::Forward);
+    if correlate {
+        kernel_fft.mapv_inplace(|x| x.conj());
+    }
+
+    let mut output = zeros_with_layout_of(array, array.raw_dim());
+    for z in 0..len_z {
+        let mut channel = array
+            .index_axis(Axis(2), z)
+            .map(|&x| Complex::new(x, 0_f64));
+        fft2(&mut channel, &mut planner, FftDirection::Forward);
+        channel *= &kernel_fft;
+        fft2(&mut channel, &mut planner, FftDirection::Inverse);
+        // the inverse FFT is not normalized
+        let scale = (len_x * len_y) as f64;
+        output
+            .index_axis_mut(Axis(2), z)
+            .assign(&channel.map(|x| x.re / scale));
+    }
+    output
+}
+
+/// FFT of a 2 dimentional array, in place, along both axes.
+fn fft2(
+    array: &mut Array2<Complex<f64>>,
+    planner: &mut FftPlanner<f64>,
+    direction: FftDirection,
+) {
+    for axis in 0..2 {
+        let fft = planner.plan_fft(array.len_of(Axis(axis)), direction);
+        let mut buffer = Vec::with_capacity(array.len_of(Axis(axis)));
+        for mut lane in array.lanes_mut(Axis(axis)) {
+            buffer.clear();
+            buffer.extend(lane.iter());
+            fft.process(&mut buffer);
+            lane.iter_mut()
+                .zip(&buffer)
+                .for_each(|(element, &value)| *element = value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{
+        s,
+        Array2,
+        Array3,
+        Ix3,
+        ShapeBuilder,
+        ShapeError,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{
+        convolve_full_direct,
+        convolve_full_fft,
+        correlate_valid_direct,
+        correlate_valid_fft,
+        pad,
+        Convolution,
+    };
+    use crate::ops::{
+        is_adjoint,
+        Boundary,
+        Gradient,
+        Norm,
+    };
+
+    const BOUNDARIES: [Boundary; 3] =
+        [Boundary::Periodic, Boundary::Neumann, Boundary::Dirichlet];
+
+    fn random_array3(shape: (usize, usize, usize)) -> Array3<f64> {
+        let mut array = Array3::zeros(shape);
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        array
+    }
+
+    fn random_kernel(shape: (usize, usize)) -> Array2<f64> {
+        let mut kernel = Array2::zeros(shape);
+        kernel.mapv_inplace(|_| rand::random::<f64>());
+        kernel
+    }
+
+    #[test]
+    fn array3_f64_convolve_returns_error_if_kernel_is_empty() {
+        let array = random_array3((10, 5, 3));
+        let kernel = Array2::zeros((0, 3));
+
+        let convolved = array.convolve(&kernel, Boundary::Neumann);
+        let convolved_adjoint =
+            array.convolve_adjoint(&kernel, Boundary::Neumann);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        assert_eq!(convolved, Err(unsupported_error.clone()));
+        assert_eq!(convolved_adjoint, Err(unsupported_error));
+    }
+
+    #[test]
+    fn array3_f64_convolve_with_identity_kernel() {
+        let array = random_array3((10, 5, 3));
+        let mut kernel = Array2::zeros((3, 3));
+        kernel[[1, 1]] = 1_f64;
+
+        for boundary in BOUNDARIES {
+            let convolved = array.convolve(&kernel, boundary).unwrap();
+
+            assert_eq!(convolved, array);
+        }
+    }
+
+    #[test]
+    fn array3_f64_convolve_with_difference_kernel_is_positive_gradient_on_axis()
+    {
+        let array = random_array3((10, 5, 3));
+        let kernel_x =
+            Array2::from_shape_vec((2, 1), vec![1_f64, -1_f64]).unwrap();
+        let kernel_y =
+            Array2::from_shape_vec((1, 2), vec![1_f64, -1_f64]).unwrap();
+
+        for boundary in BOUNDARIES {
+            let convolved_x = array.convolve(&kernel_x, boundary).unwrap();
+            let convolved_y = array.convolve(&kernel_y, boundary).unwrap();
+
+            let gradient_x = array
+                .positive_gradient_on_axis_with_boundary(0, boundary)
+                .unwrap();
+            let gradient_y = array
+                .positive_gradient_on_axis_with_boundary(1, boundary)
+                .unwrap();
+
+            assert_eq!(convolved_x, gradient_x);
+            assert_eq!(convolved_y, gradient_y);
+        }
+    }
+
+    #[test]
+    fn array3_f64_convolve_adjoint_is_adjoint_of_convolve() {
+        // small kernels are convolved directly, large ones with the FFT
+        for kernel_shape in [(3, 3), (2, 5), (9, 9), (13, 6)] {
+            let kernel = random_kernel(kernel_shape);
+
+            for boundary in BOUNDARIES {
+                let forward = |x: &Array3<f64>| x.convolve(&kernel, boundary);
+                let adjoint =
+                    |y: &Array3<f64>| y.convolve_adjoint(&kernel, boundary);
+                let shape = Ix3(10, 7, 3);
+
+                assert!(is_adjoint(forward, adjoint, shape, shape, 5, 1e-10)
+                    .unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn array3_f64_convolve_keeps_memory_layout() {
+        let c_array = random_array3((10, 7, 3));
+        let mut f_array = Array3::zeros((10, 7, 3).f());
+        f_array.assign(&c_array);
+
+        // small kernels are convolved directly, large ones with the FFT
+        for kernel_shape in [(3, 3), (9, 9)] {
+            let kernel = random_kernel(kernel_shape);
+
+            for array in [&c_array, &f_array] {
+                let convolved =
+                    array.convolve(&kernel, Boundary::Neumann).unwrap();
+                let convolved_adjoint =
+                    array.convolve_adjoint(&kernel, Boundary::Neumann).unwrap();
+
+                assert_eq!(convolved.strides(), array.strides());
+                assert_eq!(convolved_adjoint.strides(), array.strides());
+            }
+        }
+    }
+
+    #[test]
+    fn fft_and_direct_convolutions_are_the_same() {
+        let array = random_array3((10, 7, 3));
+        let kernel = random_kernel((4, 5));
+        let flipped = kernel.slice(s![..;-1, ..;-1]);
+
+        for boundary in BOUNDARIES {
+            let padded = pad(&array, kernel.dim(), boundary);
+
+            let direct = correlate_valid_direct(&padded, flipped, (10, 7));
+            let fft = correlate_valid_fft(&padded, flipped, (10, 7));
+            assert!((&fft - &direct).norm() / direct.norm() < 1e-12);
+
+            let direct = convolve_full_direct(&array, flipped);
+            let fft = convolve_full_fft(&array, flipped);
+            assert!((&fft - &direct).norm() / direct.norm() < 1e-12);
+        }
+    }
+}
@@ -1,26 +1,90 @@
-use std::ops::Sub;
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::ops::{
+    Add,
+    Sub,
+};
 
 use ndarray::{
     Array,
     Axis,
     Dimension,
     RemoveAxis,
+    ShapeBuilder,
     ShapeError,
+    Slice,
 };
 
+/// Boundary condition of the shifts (and therefore of the gradients) on an
+/// axis, i.e. how the values outside of the array are extended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Boundary {
+    /// The array wraps around, i.e. the last index is followed by the 0th
+    /// index. The gradient on the boundary is the difference between the
+    /// values on both ends of the axis.
+    Periodic,
+    /// Zero-flux boundary, the value on the boundary is repeated outside of
+    /// the array. The gradient on the boundary is zero.
+    #[default]
+    Neumann,
+    /// The values outside of the array are zero. The gradient on the boundary
+    /// is the value on the boundary.
+    Dirichlet,
+}
+
 /// Trait for calculating the gradient (derivation) on an axis of a N
 /// dimentional Array. The gradient methods are provided using the shift methods
 /// for Self which implements &Self - &Self. The gradient must be implemented
 /// such that for all X, (PG_A * B).sum() == A * NG_B.sum(), where A and B
 /// are arrays of the same shape, PG_A is the positive gradient of A on some
-/// axis X and NG_B is the negative gradient of B on that same axis X.
+/// axis X and NG_B is the negative gradient of B on that same axis X, for
+/// every [`Boundary`]. The second order operators (the Hessian and the
+/// Laplacian) are also provided, by composing the gradients.
 pub trait Gradient: Sized {
     /// Must output a same shape array shifted towards the growing indexes on
-    /// the given axis. On the boundary, the shift must be wrapping (i.e. the
-    /// last index of the given axis will become the 0th index). Must be checked
-    /// for bounds (i.e. given axis must exist in array) and size of the
-    /// given axis, as a shift cannot be performed on an axis with len < 2.
-    fn positive_shift_on_axis(&self, axis: usize) -> Result<Self, ShapeError>;
+    /// the given axis. On the boundary, the 0th index is filled according to
+    /// the given [`Boundary`]. Must be checked for bounds (i.e. given axis
+    /// must exist in array) and size of the given axis, as a shift cannot be
+    /// performed on an axis with len < 2.
+    fn positive_shift_on_axis_with_boundary(
+        &self,
+        axis: usize,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>;
+
+    /// Must output the adjoint of the positive shift with the same
+    /// [`Boundary`], i.e. a same shape array shifted towards the shrinking
+    /// indexes on the given axis, such that for all X, (PS_A * B).sum() ==
+    /// (A * NS_B).sum(), where PS_A is the positive shift of A and NS_B is the
+    /// negative shift of B on that same axis X. Must be checked for bounds
+    /// (i.e. given axis must exist in array) and size of the given axis, as a
+    /// shift cannot be performed on an axis with len < 2.
+    fn negative_shift_on_axis_with_boundary(
+        &self,
+        axis: usize,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>;
+
+    /// Outputs a same shape array shifted towards the growing indexes on
+    /// the given axis. On the boundary, the shift is wrapping (i.e. the
+    /// last index of the given axis will become the 0th index), see
+    /// [`Boundary::Periodic`].
+    fn positive_shift_on_axis(&self, axis: usize) -> Result<Self, ShapeError> {
+        self.positive_shift_on_axis_with_boundary(axis, Boundary::Periodic)
+    }
 
     /// Outputs the same shape array by shifting on the given axis and
     /// subtracting the result from self. Returns any error from shifting,
@@ -31,21 +95,37 @@ pub trait Gradient: Sized {
     /// gradient of A on some axis X, NG_B is the negative gradient of B
     /// on that same axis X, and .sum() returns a scalar with the sum of all
     /// elements of the array.
-    fn positive_gradient_on_axis(&self, axis: usize) -> Result<Self, ShapeError>
+    fn positive_gradient_on_axis_with_boundary(
+        &self,
+        axis: usize,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>
     where
         for<'x> &'x Self: Sub<Output = Self>,
     {
-        let shifted = self.positive_shift_on_axis(axis)?;
+        let shifted =
+            self.positive_shift_on_axis_with_boundary(axis, boundary)?;
 
         Ok(self - &shifted)
     }
 
-    /// Must output a same shape array shifted towards the shrinking indexes on
-    /// the given axis. On the boundary, the shift must be wrapping (i.e. the
-    /// 0th index of the given axis will become the last index). Must be checked
-    /// for bounds (i.e. given axis must exist in array) and size of the
-    /// given axis, as a shift cannot be performed on an axis with len < 2.
-    fn negative_shift_on_axis(&self, axis: usize) -> Result<Self, ShapeError>;
+    /// Same as [`positive_gradient_on_axis_with_boundary`](
+    /// Self::positive_gradient_on_axis_with_boundary) with
+    /// [`Boundary::Periodic`].
+    fn positive_gradient_on_axis(&self, axis: usize) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Sub<Output = Self>,
+    {
+        self.positive_gradient_on_axis_with_boundary(axis, Boundary::Periodic)
+    }
+
+    /// Outputs a same shape array shifted towards the shrinking indexes on
+    /// the given axis. On the boundary, the shift is wrapping (i.e. the
+    /// 0th index of the given axis will become the last index), see
+    /// [`Boundary::Periodic`].
+    fn negative_shift_on_axis(&self, axis: usize) -> Result<Self, ShapeError> {
+        self.negative_shift_on_axis_with_boundary(axis, Boundary::Periodic)
+    }
 
     /// Outputs the same shape array by shifting on the given axis and
     /// subtracting the result from self. Returns any error from shifting,
@@ -56,72 +136,277 @@ pub trait Gradient: Sized {
     /// gradient of A on some axis X, NG_B is the negative gradient of B
     /// on that same axis X, and .sum() returns a scalar with the sum of all
     /// elements of the array.
-    fn negative_gradient_on_axis(&self, axis: usize) -> Result<Self, ShapeError>
+    fn negative_gradient_on_axis_with_boundary(
+        &self,
+        axis: usize,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>
     where
         for<'x> &'x Self: Sub<Output = Self>,
     {
-        let shifted = self.negative_shift_on_axis(axis)?;
+        let shifted =
+            self.negative_shift_on_axis_with_boundary(axis, boundary)?;
 
         Ok(self - &shifted)
     }
-}
 
-impl<D: Dimension + RemoveAxis> Gradient for Array<f64, D> {
-    /// Outputs a same shape array shifted towards the growing indexes on
-    /// the given axis. On the boundary, the shift is wrapping (i.e. the
-    /// last index of the given axis will become the 0th index). The input is
-    /// checked for bounds (i.e. given axis must exist in array) and size of
-    /// the given axis, as a shift cannot be performed on an axis with len <
-    /// 2.
-    fn positive_shift_on_axis(&self, axis: usize) -> Result<Self, ShapeError> {
-        if !(axis < self.ndim()) {
-            let out_of_bounds = ndarray::ErrorKind::OutOfBounds;
-            return Err(ShapeError::from_kind(out_of_bounds));
+    /// Same as [`negative_gradient_on_axis_with_boundary`](
+    /// Self::negative_gradient_on_axis_with_boundary) with
+    /// [`Boundary::Periodic`].
+    fn negative_gradient_on_axis(&self, axis: usize) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Sub<Output = Self>,
+    {
+        self.negative_gradient_on_axis_with_boundary(axis, Boundary::Periodic)
+    }
+
+    /// Outputs the same shape array with the second derivative on the given
+    /// axes, i.e. the element of the Hessian on (`axis_a`, `axis_b`). On the
+    /// same axis (e.g. xx), it is the centered second difference
+    /// `A[i + 1] - 2 * A[i] + A[i - 1]`, that is minus the negative gradient
+    /// of the positive gradient. On different axes (e.g. xy), it is the
+    /// positive gradient on `axis_b` of the positive gradient on `axis_a`,
+    /// which is the same for both orders of the axes. Returns any error
+    /// from shifting, which must be checked for bounds (i.e. given axes
+    /// must exist in array) and size of the given axes (must be > 2).
+    fn hessian_on_axes_with_boundary(
+        &self,
+        axis_a: usize,
+        axis_b: usize,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Sub<Output = Self>,
+    {
+        let gradient =
+            self.positive_gradient_on_axis_with_boundary(axis_a, boundary)?;
+        if axis_a == axis_b {
+            let shifted = gradient
+                .negative_shift_on_axis_with_boundary(axis_a, boundary)?;
+            Ok(&shifted - &gradient)
+        } else {
+            gradient.positive_gradient_on_axis_with_boundary(axis_b, boundary)
         }
+    }
 
-        if !(self.len_of(Axis(axis)) > 1) {
-            let unsupported = ndarray::ErrorKind::Unsupported;
-            return Err(ShapeError::from_kind(unsupported));
+    /// Same as [`hessian_on_axes_with_boundary`](
+    /// Self::hessian_on_axes_with_boundary) with [`Boundary::Periodic`].
+    fn hessian_on_axes(
+        &self,
+        axis_a: usize,
+        axis_b: usize,
+    ) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Sub<Output = Self>,
+    {
+        self.hessian_on_axes_with_boundary(axis_a, axis_b, Boundary::Periodic)
+    }
+
+    /// Outputs the adjoint of [`hessian_on_axes_with_boundary`](
+    /// Self::hessian_on_axes_with_boundary) with the same axes and
+    /// [`Boundary`], such that for all A and B, (H_A * B).sum() ==
+    /// (A * HA_B).sum(). On the same axis it is the second derivative itself,
+    /// which is self-adjoint, and on different axes it is the negative
+    /// gradient on `axis_a` of the negative gradient on `axis_b`. Returns any
+    /// error from shifting.
+    fn hessian_adjoint_on_axes_with_boundary(
+        &self,
+        axis_a: usize,
+        axis_b: usize,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Sub<Output = Self>,
+    {
+        if axis_a == axis_b {
+            self.hessian_on_axes_with_boundary(axis_a, axis_b, boundary)
+        } else {
+            self.negative_gradient_on_axis_with_boundary(axis_b, boundary)?
+                .negative_gradient_on_axis_with_boundary(axis_a, boundary)
         }
+    }
 
-        let last_index_of_axis = self.len_of(Axis(axis)) - 1;
-        let (a, b) = self.view().split_at(Axis(axis), last_index_of_axis);
-        ndarray::concatenate(Axis(axis), &[b, a])
+    /// Same as [`hessian_adjoint_on_axes_with_boundary`](
+    /// Self::hessian_adjoint_on_axes_with_boundary) with
+    /// [`Boundary::Periodic`].
+    fn hessian_adjoint_on_axes(
+        &self,
+        axis_a: usize,
+        axis_b: usize,
+    ) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Sub<Output = Self>,
+    {
+        self.hessian_adjoint_on_axes_with_boundary(
+            axis_a,
+            axis_b,
+            Boundary::Periodic,
+        )
     }
 
-    /// Outputs a same shape array shifted towards the shrinking indexes on
-    /// the given axis. On the boundary, the shift is wrapping (i.e. the
-    /// 0th index of the given axis will become the last index). The input is
-    /// checked for bounds (i.e. given axis must exist in array) and size of
-    /// the given axis, as a shift cannot be performed on an axis with len <
-    /// 2.
-    fn negative_shift_on_axis(&self, axis: usize) -> Result<Self, ShapeError> {
-        if !(axis < self.ndim()) {
-            let out_of_bounds = ndarray::ErrorKind::OutOfBounds;
-            return Err(ShapeError::from_kind(out_of_bounds));
+    /// Outputs the same shape array with the Laplacian on the given axes,
+    /// i.e. the sum of the second derivatives on each axis (e.g. xx + yy for
+    /// the axes 0 and 1 of an image). The Laplacian is self-adjoint, so it
+    /// is also its own adjoint. Returns any error from shifting, and an
+    /// unsupported error if no axis is given.
+    fn laplacian_on_axes_with_boundary(
+        &self,
+        axes: &[usize],
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Add<Output = Self> + Sub<Output = Self>,
+    {
+        let (&first, rest) = axes.split_first().ok_or_else(|| {
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported)
+        })?;
+
+        let mut laplacian =
+            self.hessian_on_axes_with_boundary(first, first, boundary)?;
+        for &axis in rest {
+            let second_derivative =
+                self.hessian_on_axes_with_boundary(axis, axis, boundary)?;
+            laplacian = &laplacian + &second_derivative;
         }
+        Ok(laplacian)
+    }
 
-        if !(self.len_of(Axis(axis)) > 1) {
-            let unsupported = ndarray::ErrorKind::Unsupported;
-            return Err(ShapeError::from_kind(unsupported));
+    /// Same as [`laplacian_on_axes_with_boundary`](
+    /// Self::laplacian_on_axes_with_boundary) with [`Boundary::Periodic`].
+    fn laplacian_on_axes(&self, axes: &[usize]) -> Result<Self, ShapeError>
+    where
+        for<'x> &'x Self: Add<Output = Self> + Sub<Output = Self>,
+    {
+        self.laplacian_on_axes_with_boundary(axes, Boundary::Periodic)
+    }
+}
+
+impl<D: Dimension + RemoveAxis> Gradient for Array<f64, D> {
+    /// Outputs a same shape array shifted towards the growing indexes on
+    /// the given axis. On the boundary, the 0th index is the last index for
+    /// [`Boundary::Periodic`], the 0th index for [`Boundary::Neumann`], and
+    /// zero for [`Boundary::Dirichlet`]. The
+    /// input is checked for bounds (i.e. given axis must exist in array) and
+    /// size of the given axis, as a shift cannot be performed on an axis
+    /// with len < 2. The output keeps the memory layout of the input.
+    fn positive_shift_on_axis_with_boundary(
+        &self,
+        axis: usize,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError> {
+        check_shift_on_axis(self, axis)?;
+
+        let mut shifted = zeros_with_same_layout(self);
+        shifted
+            .slice_axis_mut(Axis(axis), Slice::from(1..))
+            .assign(&self.slice_axis(Axis(axis), Slice::from(..-1)));
+        let mut first = shifted.slice_axis_mut(Axis(axis), Slice::from(..1));
+        match boundary {
+            Boundary::Periodic => {
+                first.assign(&self.slice_axis(Axis(axis), Slice::from(-1..)))
+            },
+            Boundary::Neumann => {
+                first.assign(&self.slice_axis(Axis(axis), Slice::from(..1)))
+            },
+            Boundary::Dirichlet => {},
         }
+        Ok(shifted)
+    }
+
+    /// Outputs a same shape array shifted towards the shrinking indexes on
+    /// the given axis, which is the adjoint of the positive shift. On the
+    /// boundary, the last index is the 0th index for [`Boundary::Periodic`],
+    /// and zero otherwise, while for [`Boundary::Neumann`] the 0th index is
+    /// also added to the 0th index of the output. The input is checked for
+    /// bounds (i.e. given axis must exist in array) and size of the given
+    /// axis, as a shift cannot be performed on an axis with len < 2. The
+    /// output keeps the memory layout of the input.
+    fn negative_shift_on_axis_with_boundary(
+        &self,
+        axis: usize,
+        boundary: Boundary,
+    ) -> Result<Self, ShapeError> {
+        check_shift_on_axis(self, axis)?;
+
+        let mut shifted = zeros_with_same_layout(self);
+        shifted
+            .slice_axis_mut(Axis(axis), Slice::from(..-1))
+            .assign(&self.slice_axis(Axis(axis), Slice::from(1..)));
+        match boundary {
+            Boundary::Periodic => shifted
+                .slice_axis_mut(Axis(axis), Slice::from(-1..))
+                .assign(&self.slice_axis(Axis(axis), Slice::from(..1))),
+            Boundary::Neumann => {
+                let mut first =
+                    shifted.slice_axis_mut(Axis(axis), Slice::from(..1));
+                first += &self.slice_axis(Axis(axis), Slice::from(..1));
+            },
+            Boundary::Dirichlet => {},
+        }
+        Ok(shifted)
+    }
+}
+
+/// Checks that the given axis exists in the array and that its len is at
+/// least 2, as required by the shifts.
+fn check_shift_on_axis<D: Dimension>(
+    array: &Array<f64, D>,
+    axis: usize,
+) -> Result<(), ShapeError> {
+    if axis >= array.ndim() {
+        let out_of_bounds = ndarray::ErrorKind::OutOfBounds;
+        return Err(ShapeError::from_kind(out_of_bounds));
+    }
 
-        let (a, b) = self.view().split_at(Axis(axis), 1);
-        ndarray::concatenate(Axis(axis), &[b, a])
+    if array.len_of(Axis(axis)) < 2 {
+        let unsupported = ndarray::ErrorKind::Unsupported;
+        return Err(ShapeError::from_kind(unsupported));
     }
+
+    Ok(())
+}
+
+/// Allocates a zeroed array of the same shape as the input, in column-major
+/// (Fortran) memory order if the input is, or in row-major (C) memory order
+/// otherwise, so that the shifts keep the memory layout of their input.
+pub(crate) fn zeros_with_same_layout<D: Dimension>(
+    array: &Array<f64, D>,
+) -> Array<f64, D> {
+    zeros_with_layout_of(array, array.raw_dim())
+}
+
+/// Same as [`zeros_with_same_layout`], with the given shape.
+pub(crate) fn zeros_with_layout_of<D: Dimension>(
+    array: &Array<f64, D>,
+    dim: D,
+) -> Array<f64, D> {
+    let column_major =
+        !array.is_standard_layout() && array.t().is_standard_layout();
+    Array::zeros(dim.set_f(column_major))
 }
 
 #[cfg(test)]
 mod test {
     use ndarray::{
         Array,
+        Array3,
         Axis,
+        Ix3,
+        ShapeBuilder,
         ShapeError,
+        Slice,
     };
     use pretty_assertions::assert_eq;
     use rand::seq::IteratorRandom;
 
-    use super::Gradient;
+    use super::{
+        Boundary,
+        Gradient,
+    };
+    use crate::ops::is_adjoint;
+
+    const BOUNDARIES: [Boundary; 3] =
+        [Boundary::Periodic, Boundary::Neumann, Boundary::Dirichlet];
 
     #[test]
     fn array_f64_positive_shift_on_axis_returns_error_if_axis_is_out_of_bounds()
@@ -289,6 +574,107 @@ mod test {
         }
     }
 
+    #[test]
+    fn array_f64_shift_on_axis_keeps_memory_layout() {
+        let mut rng = rand::thread_rng();
+        // Shift only supported for axis len > 1
+        let mut random_axis_len = || (2..10).choose(&mut rng).unwrap();
+
+        // Array0 has no axes
+        for dim in 1..=7 {
+            let shape: Vec<usize> =
+                (0..dim).map(|_| random_axis_len()).collect();
+            let c_array = Array::<f64, _>::zeros(shape.clone());
+            let f_array = Array::<f64, _>::zeros(shape.f());
+
+            for axis in 0..dim {
+                let c_positive = c_array.positive_shift_on_axis(axis).unwrap();
+                let c_negative = c_array.negative_shift_on_axis(axis).unwrap();
+                let f_positive = f_array.positive_shift_on_axis(axis).unwrap();
+                let f_negative = f_array.negative_shift_on_axis(axis).unwrap();
+
+                assert_eq!(c_positive.strides(), c_array.strides());
+                assert_eq!(c_negative.strides(), c_array.strides());
+                assert_eq!(f_positive.strides(), f_array.strides());
+                assert_eq!(f_negative.strides(), f_array.strides());
+            }
+        }
+    }
+
+    #[test]
+    fn array_f64_positive_gradient_on_axis_with_boundary_on_0th_index() {
+        let mut array = Array::<f64, _>::zeros((4, 3, 2));
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+
+        for axis in 0..3 {
+            let first = |a: &Array<f64, _>| {
+                a.slice_axis(Axis(axis), Slice::from(..1)).to_owned()
+            };
+            let last = |a: &Array<f64, _>| {
+                a.slice_axis(Axis(axis), Slice::from(-1..)).to_owned()
+            };
+
+            let periodic = array
+                .positive_gradient_on_axis_with_boundary(
+                    axis,
+                    Boundary::Periodic,
+                )
+                .unwrap();
+            let neumann = array
+                .positive_gradient_on_axis_with_boundary(
+                    axis,
+                    Boundary::Neumann,
+                )
+                .unwrap();
+            let dirichlet = array
+                .positive_gradient_on_axis_with_boundary(
+                    axis,
+                    Boundary::Dirichlet,
+                )
+                .unwrap();
+
+            assert_eq!(first(&periodic), first(&array) - last(&array));
+            assert_eq!(first(&neumann), first(&array) * 0.0);
+            assert_eq!(first(&dirichlet), first(&array));
+        }
+    }
+
+    #[test]
+    fn array_f64_negative_gradient_on_axis_with_boundary_is_dual_operator_of_positive_gradient_on_axis_with_boundary(
+    ) {
+        let mut rng = rand::thread_rng();
+        // Shift only supported for axis len > 1
+        let mut random_axis_len = || (2..10).choose(&mut rng).unwrap();
+
+        // Array0 has no axes
+        for dim in 1..=7 {
+            let shape: Vec<usize> =
+                (0..dim).map(|_| random_axis_len()).collect();
+            let mut array_a = Array::<f64, _>::zeros(shape.clone());
+            array_a.mapv_inplace(|_| rand::random::<u8>() as f64);
+            let mut array_b = Array::<f64, _>::zeros(shape);
+            array_b.mapv_inplace(|_| rand::random::<u8>() as f64);
+
+            for boundary in
+                [Boundary::Periodic, Boundary::Neumann, Boundary::Dirichlet]
+            {
+                for axis in 0..dim {
+                    let pos_a = array_a
+                        .positive_gradient_on_axis_with_boundary(axis, boundary)
+                        .unwrap();
+                    let neg_b = array_b
+                        .negative_gradient_on_axis_with_boundary(axis, boundary)
+                        .unwrap();
+
+                    assert_eq!(
+                        (pos_a * &array_b).sum(),
+                        (&array_a * neg_b).sum()
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn array_f64_negative_gradient_on_axis_is_dual_operator_of_positive_gradient_on_axis(
     ) {
@@ -313,27 +699,115 @@ mod test {
             }
         }
     }
-}
 
-#[cfg(test)]
-mod bench {
-    use ndarray::Array3;
+    #[test]
+    fn array_f64_hessian_on_axes_returns_error_if_axis_is_out_of_bounds() {
+        let array = Array::<f64, _>::zeros((4, 3, 2));
+
+        let hessian = array.hessian_on_axes(0, 3);
+        let adjoint = array.hessian_adjoint_on_axes(3, 3);
+        let laplacian = array.laplacian_on_axes(&[0, 3]);
+
+        let out_of_bounds_error =
+            ShapeError::from_kind(ndarray::ErrorKind::OutOfBounds);
+        assert_eq!(hessian, Err(out_of_bounds_error.clone()));
+        assert_eq!(adjoint, Err(out_of_bounds_error.clone()));
+        assert_eq!(laplacian, Err(out_of_bounds_error));
+    }
+
+    #[test]
+    fn array_f64_hessian_on_axes_returns_error_if_axis_len_is_not_gt_1() {
+        let array = Array::<f64, _>::zeros((4, 3, 1));
+
+        let hessian = array.hessian_on_axes(2, 0);
+        let laplacian = array.laplacian_on_axes(&[0, 2]);
+        let no_axes = array.laplacian_on_axes(&[]);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        assert_eq!(hessian, Err(unsupported_error.clone()));
+        assert_eq!(laplacian, Err(unsupported_error.clone()));
+        assert_eq!(no_axes, Err(unsupported_error));
+    }
 
-    use super::Gradient;
+    #[test]
+    fn array_f64_hessian_on_axes() {
+        let mut array = Array3::<f64>::zeros((5, 4, 2));
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+
+        let xx = array.hessian_on_axes(0, 0).unwrap();
+        let xy = array.hessian_on_axes(0, 1).unwrap();
+        let yx = array.hessian_on_axes(1, 0).unwrap();
+
+        let (len_x, len_y, _) = array.dim();
+        let at = |x: usize, y: usize, z: usize| {
+            array[[(x + len_x) % len_x, (y + len_y) % len_y, z]]
+        };
+        for ((x, y, z), &value) in xx.indexed_iter() {
+            let test_value =
+                at(x + 1, y, z) - 2.0 * at(x, y, z) + at(x + len_x - 1, y, z);
+            assert_eq!(value, test_value);
+        }
+        for ((x, y, z), &value) in xy.indexed_iter() {
+            let test_value =
+                at(x, y, z) - at(x + len_x - 1, y, z) - at(x, y + len_y - 1, z)
+                    + at(x + len_x - 1, y + len_y - 1, z);
+            assert_eq!(value, test_value);
+        }
+        assert_eq!(xy, yx);
+    }
 
-    #[bench]
-    fn array_f64_positive_gradient_on_axis(bench: &mut test::Bencher) {
-        let mut a = Array3::zeros((1024, 768, 3));
-        a.mapv_inplace(|_| rand::random::<u8>() as f64);
+    #[test]
+    fn array_f64_laplacian_on_axes_is_sum_of_second_derivatives() {
+        let mut array = Array3::<f64>::zeros((5, 4, 2));
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+
+        for boundary in BOUNDARIES {
+            let laplacian =
+                array.laplacian_on_axes_with_boundary(&[0, 1], boundary);
 
-        bench.iter(|| test::black_box(a.positive_gradient_on_axis(2).unwrap()));
+            let xx = array.hessian_on_axes_with_boundary(0, 0, boundary);
+            let yy = array.hessian_on_axes_with_boundary(1, 1, boundary);
+            assert_eq!(laplacian.unwrap(), xx.unwrap() + yy.unwrap());
+        }
     }
 
-    #[bench]
-    fn array_f64_negative_gradient_on_axis(bench: &mut test::Bencher) {
-        let mut a = Array3::zeros((1024, 768, 3));
-        a.mapv_inplace(|_| rand::random::<u8>() as f64);
+    #[test]
+    fn array_f64_laplacian_on_axes_of_constant_is_zero_except_for_dirichlet() {
+        let array = Array3::<f64>::from_elem((5, 4, 2), 3.0);
 
-        bench.iter(|| test::black_box(a.negative_gradient_on_axis(2).unwrap()));
+        for boundary in [Boundary::Periodic, Boundary::Neumann] {
+            let laplacian = array
+                .laplacian_on_axes_with_boundary(&[0, 1], boundary)
+                .unwrap();
+
+            assert_eq!(laplacian, Array3::zeros((5, 4, 2)));
+        }
+    }
+
+    #[test]
+    fn array_f64_hessian_adjoint_on_axes_is_adjoint_of_hessian_on_axes() {
+        let shape = Ix3(6, 5, 3);
+        for boundary in BOUNDARIES {
+            for (axis_a, axis_b) in [(0, 0), (0, 1), (1, 0), (1, 1), (2, 0)] {
+                let forward = |x: &Array3<f64>| {
+                    x.hessian_on_axes_with_boundary(axis_a, axis_b, boundary)
+                };
+                let adjoint = |y: &Array3<f64>| {
+                    y.hessian_adjoint_on_axes_with_boundary(
+                        axis_a, axis_b, boundary,
+                    )
+                };
+
+                assert!(is_adjoint(forward, adjoint, shape, shape, 5, 1e-12)
+                    .unwrap());
+            }
+
+            let laplacian = |x: &Array3<f64>| {
+                x.laplacian_on_axes_with_boundary(&[0, 1], boundary)
+            };
+            assert!(is_adjoint(laplacian, laplacian, shape, shape, 5, 1e-12)
+                .unwrap());
+        }
     }
 }
@@ -1,9 +1,63 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Operators used by the solvers. [`ImageArray`](crate::ImageArray) is
+//! generic over any type implementing these traits, and they are implemented
+//! for `ndarray`'s `Array<f64, D>`, so custom solvers can be written against
+//! them and they can be implemented for other array types.
+//!
+//! ```rust
+//! use image_recovery::{
+//!     ndarray::Array3,
+//!     ops::{
+//!         Boundary,
+//!         Gradient,
+//!         Norm,
+//!     },
+//! };
+//!
+//! let mut array = Array3::<f64>::zeros((10, 5, 3));
+//! array[[4, 2, 0]] = 1.0;
+//!
+//! // finite differences on the x axis, with zero-flux boundary
+//! let gradient = array
+//!     .positive_gradient_on_axis_with_boundary(0, Boundary::Neumann)
+//!     .unwrap();
+//!
+//! assert_eq!(gradient.norm(), 2_f64.sqrt());
+//! ```
+#[cfg(any(feature = "adjoint", test))]
+mod adjoint;
 mod average;
+mod convolution;
 mod gradient;
 mod norm;
+mod sampling;
 mod vector_len;
 
+#[cfg(any(feature = "adjoint", test))]
+pub use adjoint::{
+    adjoint_mismatch,
+    is_adjoint,
+};
 pub use average::Average;
-pub use gradient::Gradient;
+pub use convolution::Convolution;
+pub use gradient::{
+    Boundary,
+    Gradient,
+};
 pub use norm::Norm;
+pub use sampling::Sampling;
 pub use vector_len::VectorLen;
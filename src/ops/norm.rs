@@ -1,3 +1,18 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
 use ndarray::{
     Array,
     Dimension,
@@ -5,6 +20,8 @@ use ndarray::{
 
 /// Trait for calculating the Euclidean Norm of an array
 pub trait Norm {
+    /// Calculates the Euclidean Norm of all elements of the array, as a
+    /// single vector.
     fn norm(&self) -> f64;
 }
 
@@ -0,0 +1,296 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use ndarray::{
+    s,
+    Array1,
+    Array3,
+    Axis,
+    ShapeError,
+};
+
+/// Trait for changing the resolution of an image on the x and y axes (axes 0
+/// and 1) by an integer factor per axis, for each channel (axis 2)
+/// independently. The downsampling must be implemented such that for all A
+/// and B, (D_A * B).sum() == (A * DA_B).sum(), where A is an array of the
+/// original shape, B is an array of the downsampled shape, D_A is the
+/// downsampling of A and DA_B is the adjoint downsampling of B.
+pub trait Sampling: Sized {
+    /// Must output the average of each block of `factor` elements, i.e. an
+    /// array with `len / factor` elements (rounded up) on each axis. When the
+    /// len of an axis is not divisible by its factor, the last block of the
+    /// axis is smaller, and is averaged over the elements it has. Must be
+    /// checked for the factors being greater than 0.
+    fn downsample(&self, factor: (usize, usize)) -> Result<Self, ShapeError>;
+
+    /// Must output the adjoint of [`downsample`](Self::downsample) with the
+    /// same factor, i.e. an array of the given shape on the x and y axes,
+    /// where each element is the element of its block divided by the number
+    /// of elements in the block. Must be checked for the factors being
+    /// greater than 0 and for the shape being compatible with the factor,
+    /// i.e. downsampling an array of the given shape must result in an array
+    /// with the shape of `self`.
+    fn downsample_adjoint(
+        &self,
+        factor: (usize, usize),
+        shape: (usize, usize),
+    ) -> Result<Self, ShapeError>;
+
+    /// Must output an array of the given shape on the x and y axes, where
+    /// each element is the element of its block, i.e. the values of `self`
+    /// are replicated, such that downsampling the output with the same
+    /// factor results in `self`. Must be checked for the factors being
+    /// greater than 0 and for the shape being compatible with the factor.
+    fn upsample(
+        &self,
+        factor: (usize, usize),
+        shape: (usize, usize),
+    ) -> Result<Self, ShapeError>;
+}
+
+impl Sampling for Array3<f64> {
+    /// Outputs the average of each block of `factor` elements on the x and y
+    /// axes, for each channel. The last block of an axis which len is not
+    /// divisible by its factor is averaged over the elements it has.
+    fn downsample(&self, factor: (usize, usize)) -> Result<Self, ShapeError> {
+        check_factor(factor)?;
+
+        let (len_x, len_y, len_z) = self.dim();
+        let shape = (
+            downsampled_len(len_x, factor.0),
+            downsampled_len(len_y, factor.1),
+        );
+        let mut downsampled = Array3::zeros((shape.0, shape.1, len_z));
+        for i in 0..factor.0.min(len_x) {
+            for j in 0..factor.1.min(len_y) {
+                let block_elements =
+                    self.slice(s![i..;factor.0, j..;factor.1, ..]);
+                let (block_x, block_y, _) = block_elements.dim();
+                let mut blocks =
+                    downsampled.slice_mut(s![..block_x, ..block_y, ..]);
+                blocks += &block_elements;
+            }
+        }
+        downsampled /= &block_len(len_x, len_y, factor);
+
+        Ok(downsampled)
+    }
+
+    /// Outputs an array of the given shape on the x and y axes, where each
+    /// element is the element of its block divided by the number of elements
+    /// in the block, which is the adjoint of the downsampling.
+    fn downsample_adjoint(
+        &self,
+        factor: (usize, usize),
+        shape: (usize, usize),
+    ) -> Result<Self, ShapeError> {
+        check_sampling(self, factor, shape)?;
+
+        let scaled = self / &block_len(shape.0, shape.1, factor);
+        scaled.upsample(factor, shape)
+    }
+
+    /// Outputs an array of the given shape on the x and y axes, where each
+    /// element is the element of its block.
+    fn upsample(
+        &self,
+        factor: (usize, usize),
+        shape: (usize, usize),
+    ) -> Result<Self, ShapeError> {
+        check_sampling(self, factor, shape)?;
+
+        let mut upsampled =
+            Array3::zeros((shape.0, shape.1, self.len_of(Axis(2))));
+        for i in 0..factor.0.min(shape.0) {
+            for j in 0..factor.1.min(shape.1) {
+                let mut block_elements =
+                    upsampled.slice_mut(s![i..;factor.0, j..;factor.1, ..]);
+                let (block_x, block_y, _) = block_elements.dim();
+                block_elements.assign(&self.slice(s![
+                    ..block_x,
+                    ..block_y,
+                    ..
+                ]));
+            }
+        }
+
+        Ok(upsampled)
+    }
+}
+
+/// Checks that the factors are greater than 0.
+fn check_factor(factor: (usize, usize)) -> Result<(), ShapeError> {
+    if factor.0 < 1 || factor.1 < 1 {
+        let unsupported = ndarray::ErrorKind::Unsupported;
+        return Err(ShapeError::from_kind(unsupported));
+    }
+
+    Ok(())
+}
+
+/// Checks that the factors are greater than 0, and that downsampling an
+/// array of the given shape results in an array of the shape of `array`.
+fn check_sampling(
+    array: &Array3<f64>,
+    factor: (usize, usize),
+    shape: (usize, usize),
+) -> Result<(), ShapeError> {
+    check_factor(factor)?;
+
+    if downsampled_len(shape.0, factor.0) != array.len_of(Axis(0))
+        || downsampled_len(shape.1, factor.1) != array.len_of(Axis(1))
+    {
+        let incompatible = ndarray::ErrorKind::IncompatibleShape;
+        return Err(ShapeError::from_kind(incompatible));
+    }
+
+    Ok(())
+}
+
+/// Len of an axis after downsampling it by the given factor.
+fn downsampled_len(len: usize, factor: usize) -> usize {
+    len.div_ceil(factor)
+}
+
+/// Number of elements in each block for an array of the given len on the x
+/// and y axes, broadcastable to the downsampled array.
+fn block_len(
+    len_x: usize,
+    len_y: usize,
+    factor: (usize, usize),
+) -> Array3<f64> {
+    let block_len_on_axis = |len: usize, factor: usize| {
+        Array1::from_iter(
+            (0..downsampled_len(len, factor))
+                .map(|block| (len - block * factor).min(factor) as f64),
+        )
+    };
+    let block_x = block_len_on_axis(len_x, factor.0).insert_axis(Axis(1));
+    let block_y = block_len_on_axis(len_y, factor.1).insert_axis(Axis(0));
+
+    (&block_x * &block_y).insert_axis(Axis(2))
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{
+        s,
+        Array3,
+        Axis,
+        Ix3,
+        ShapeError,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::Sampling;
+    use crate::ops::is_adjoint;
+
+    fn random_array3(shape: (usize, usize, usize)) -> Array3<f64> {
+        let mut array = Array3::zeros(shape);
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        array
+    }
+
+    #[test]
+    fn array3_f64_downsample_returns_error_if_factor_is_0() {
+        let array = random_array3((10, 5, 3));
+
+        let downsampled = array.downsample((0, 2));
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        assert_eq!(downsampled, Err(unsupported_error));
+    }
+
+    #[test]
+    fn array3_f64_upsample_returns_error_if_shape_is_incompatible() {
+        let array = random_array3((5, 3, 3));
+
+        let upsampled = array.upsample((2, 2), (10, 7));
+        let adjoint = array.downsample_adjoint((2, 2), (12, 6));
+
+        let incompatible_shape_error =
+            ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        assert_eq!(upsampled, Err(incompatible_shape_error.clone()));
+        assert_eq!(adjoint, Err(incompatible_shape_error));
+    }
+
+    #[test]
+    fn array3_f64_downsample() {
+        let array = random_array3((10, 7, 3));
+
+        let downsampled = array.downsample((3, 2)).unwrap();
+
+        let mut test_downsampled = Array3::zeros((4, 4, 3));
+        for x in 0..4 {
+            for y in 0..4 {
+                let block = array.slice(s![
+                    3 * x..(3 * x + 3).min(10),
+                    2 * y..(2 * y + 2).min(7),
+                    ..
+                ]);
+                let block_len =
+                    (block.len_of(Axis(0)) * block.len_of(Axis(1))) as f64;
+                let mean =
+                    block.sum_axis(Axis(0)).sum_axis(Axis(0)) / block_len;
+                test_downsampled.slice_mut(s![x, y, ..]).assign(&mean);
+            }
+        }
+
+        assert!(
+            (&downsampled - &test_downsampled).mapv(f64::abs).sum() < 1e-10
+        );
+    }
+
+    #[test]
+    fn array3_f64_downsample_of_upsample_is_identity() {
+        let array = random_array3((4, 3, 3));
+
+        for shape in [(8_usize, 6_usize), (7, 5), (10, 9)] {
+            let factor = (shape.0.div_ceil(4), shape.1.div_ceil(3));
+
+            let upsampled = array.upsample(factor, shape).unwrap();
+            let downsampled = upsampled.downsample(factor).unwrap();
+
+            assert_eq!(downsampled, array);
+        }
+    }
+
+    #[test]
+    fn array3_f64_downsample_adjoint_is_adjoint_of_downsample() {
+        // divisible and non-divisible shapes
+        for (shape, factor) in [
+            ((8_usize, 6_usize), (2_usize, 2_usize)),
+            ((10, 7), (3, 2)),
+            ((5, 9), (5, 4)),
+            ((4, 4), (1, 1)),
+        ] {
+            let downsampled_shape =
+                (shape.0.div_ceil(factor.0), shape.1.div_ceil(factor.1));
+            let forward = |x: &Array3<f64>| x.downsample(factor);
+            let adjoint = |y: &Array3<f64>| y.downsample_adjoint(factor, shape);
+
+            assert!(is_adjoint(
+                forward,
+                adjoint,
+                Ix3(shape.0, shape.1, 3),
+                Ix3(downsampled_shape.0, downsampled_shape.1, 3),
+                5,
+                1e-12
+            )
+            .unwrap());
+        }
+    }
+}
@@ -1,3 +1,18 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
 use ndarray::{
     Array,
     Axis,
@@ -9,7 +24,9 @@ use ndarray::{
 /// Trait for calculating the lengths of two vectors
 pub trait VectorLen: Sized {
     /// Calculates the vector lenght on the given axis for two inputs. The
-    /// Output must be 1 dimension smaller.
+    /// Output must be 1 dimension smaller, i.e. the given axis must be
+    /// reduced to len 1. Must be checked for bounds (i.e. given axis must
+    /// exist in array).
     fn vector_len_on_axis(
         &self,
         other: &Self,
@@ -34,7 +51,7 @@ impl<D: Dimension + RemoveAxis> VectorLen for Array<f64, D> {
         other: &Self,
         axis: usize,
     ) -> Result<Self, ShapeError> {
-        if !(axis < self.ndim()) {
+        if axis >= self.ndim() {
             let out_of_bounds = ndarray::ErrorKind::OutOfBounds;
             return Err(ShapeError::from_kind(out_of_bounds));
         }
@@ -95,20 +112,3 @@ mod test {
         }
     }
 }
-
-#[cfg(test)]
-mod bench {
-    use ndarray::Array3;
-
-    use super::VectorLen;
-
-    #[bench]
-    fn array_f64_vector_len_on_axis(bench: &mut test::Bencher) {
-        let mut a = Array3::zeros((1024, 768, 3));
-        let mut b = Array3::zeros((1024, 768, 3));
-        a.mapv_inplace(|_| rand::random::<u8>() as f64);
-        b.mapv_inplace(|_| rand::random::<u8>() as f64);
-
-        bench.iter(|| test::black_box(a.vector_len_on_axis(&b, 2).unwrap()));
-    }
-}
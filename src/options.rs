@@ -0,0 +1,340 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Settings of the solvers, kept apart from the image they are used with.
+use std::sync::Arc;
+
+use crate::ops::Boundary;
+
+/// Total variation used as regularizer by the denoising solvers, which
+/// determines how the finite differences on the x and y axes, and on the
+/// channels of the image, are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TotalVariation {
+    /// The Euclidean norm of the finite differences on both axes and all
+    /// channels of each pixel, i.e. the Frobenius norm of the Jacobian of
+    /// the pixel. Edges are smoothed equally in all directions, and the
+    /// channels are coupled.
+    #[default]
+    Isotropic,
+    /// The sum of the absolute values of the finite differences, i.e. the
+    /// axes (and channels) are penalized separately, which keeps edges
+    /// aligned with the x and y axes sharper, e.g. for document scans and
+    /// man-made scenes.
+    Anisotropic,
+    /// The sum over the channels of the Euclidean norm of the finite
+    /// differences on both axes, i.e. isotropic total variation on each
+    /// channel independently, as if each channel was denoised separately.
+    ChannelByChannel,
+    /// The nuclear norm (sum of the singular values) of the Jacobian of each
+    /// pixel, which couples the channels more strongly than
+    /// [`TotalVariation::Isotropic`], aligning the edges across channels.
+    Nuclear,
+}
+
+/// How the solvers handle the alpha channel of an image, which is the last
+/// channel of the array, e.g. when converted from an `RgbaImage`. See
+/// [`with_alpha_policy`](crate::ImageArray::with_alpha_policy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaPolicy {
+    /// Only the color channels are denoised, and the alpha channel is left
+    /// untouched.
+    #[default]
+    Untouched,
+    /// The alpha channel is denoised separately from the color channels,
+    /// i.e. as a separate grayscale image, with the same settings.
+    Separate,
+    /// Only the color channels are denoised, and the alpha channel is used
+    /// as a per-pixel weight of the fidelity term (see
+    /// [`with_lambda_map`](crate::ImageArray::with_lambda_map)), with weight
+    /// 1 for fully opaque pixels (alpha 255 for 8-bit images). Transparent
+    /// pixels are then mostly filled in from their neighbours, as their
+    /// color does not matter. The alpha channel is left untouched. Note that
+    /// [`denoise_fista`](crate::ImageArray::denoise_fista) converges slowly
+    /// for images with fully transparent pixels, as its step size is given
+    /// by the smallest weight.
+    FidelityWeight,
+}
+
+/// Settings of the solvers of an `ImageArray`, see
+/// [`options`](crate::ImageArray::options). They are set on the image with
+/// the `with_*` methods of `ImageArray`, or all at once with
+/// [`with_options`](crate::ImageArray::with_options).
+///
+/// The lambda map and the edge weights are shared between the copies of
+/// the options (e.g. of the outputs of the solvers), so cloning the options
+/// does not copy them.
+#[derive(Debug)]
+pub struct SolverOptions<T> {
+    boundary: Boundary,
+    total_variation: TotalVariation,
+    lambda_map: Option<Arc<T>>,
+    edge_weights: Option<Arc<T>>,
+    guide: Option<(Arc<T>, f64)>,
+    alpha_policy: Option<AlphaPolicy>,
+    observation_mask: Option<Arc<T>>,
+}
+
+// not derived, which would require `T: Clone` for cloning the shared arrays
+impl<T> Clone for SolverOptions<T> {
+    fn clone(&self) -> Self {
+        Self {
+            boundary: self.boundary,
+            total_variation: self.total_variation,
+            lambda_map: self.lambda_map.clone(),
+            edge_weights: self.edge_weights.clone(),
+            guide: self.guide.clone(),
+            alpha_policy: self.alpha_policy,
+            observation_mask: self.observation_mask.clone(),
+        }
+    }
+}
+
+impl<T> Default for SolverOptions<T> {
+    fn default() -> Self {
+        Self {
+            boundary: Boundary::default(),
+            total_variation: TotalVariation::default(),
+            lambda_map: None,
+            edge_weights: None,
+            guide: None,
+            alpha_policy: None,
+            observation_mask: None,
+        }
+    }
+}
+
+impl<T> SolverOptions<T> {
+    /// Returns the boundary condition used by the solvers, which is
+    /// [`Boundary::Neumann`] unless set otherwise.
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    /// Sets the boundary condition of the gradients on the x and y axes
+    /// used by the solvers.
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    /// Returns the total variation used by the solvers.
+    pub fn total_variation(&self) -> TotalVariation {
+        self.total_variation
+    }
+
+    /// Sets the total variation used by the solvers.
+    pub fn with_total_variation(
+        mut self,
+        total_variation: TotalVariation,
+    ) -> Self {
+        self.total_variation = total_variation;
+        self
+    }
+
+    /// Returns the lambda map used by the solvers, if any.
+    pub fn lambda_map(&self) -> Option<&T> {
+        self.lambda_map.as_deref()
+    }
+
+    /// Sets a lambda map used by the solvers, which scales the `lambda`
+    /// given to the solvers for each pixel (or each pixel and channel), so
+    /// that regions with a lower SNR can be denoised harder than the rest of
+    /// the image in a single solve. A map of ones is the same as no map. The
+    /// values of the map must be positive and finite, and the map must be
+    /// broadcastable to the shape of the image, e.g. `(width, height, 1)` to
+    /// use the same value for every channel of a pixel or `(width, height,
+    /// channels)`, otherwise the solvers return an error.
+    ///
+    /// Values which are not observed at all (e.g. the colors which are not
+    /// captured through the color filter array in
+    /// [`demosaic`](crate::ImageArray::demosaic)) are not marked by zeros in
+    /// the lambda map, but by a separate internal mask, which is only
+    /// accepted by the solvers which can remove the data fidelity term.
+    pub fn with_lambda_map(mut self, lambda_map: T) -> Self {
+        self.lambda_map = Some(Arc::new(lambda_map));
+        self
+    }
+
+    /// Removes the lambda map, so that the solvers use the same `lambda`
+    /// for every pixel.
+    pub fn without_lambda_map(mut self) -> Self {
+        self.lambda_map = None;
+        self
+    }
+
+    /// Returns the edge weights of the total variation used by the solvers,
+    /// if set with [`with_edge_weights`](Self::with_edge_weights). The
+    /// weights of a guide are computed by the solvers, see
+    /// [`guide`](Self::guide).
+    pub fn edge_weights(&self) -> Option<&T> {
+        self.edge_weights.as_deref()
+    }
+
+    /// Sets the edge weights of the total variation used by the solvers,
+    /// i.e. the radius of the dual projection for each pixel, which reduces
+    /// the regularization where it is smaller than 1. The values must be
+    /// positive and finite, and the weights must be broadcastable to the
+    /// shape of the image, e.g. `(width, height, 1)`, otherwise the solvers
+    /// return an error. Replaces the guide, if any. See
+    /// [`with_guide`](crate::ImageArray::with_guide) for computing them from
+    /// a guide image.
+    pub fn with_edge_weights(mut self, edge_weights: T) -> Self {
+        self.edge_weights = Some(Arc::new(edge_weights));
+        self.guide = None;
+        self
+    }
+
+    /// Removes the edge weights (and the guide, if any), so that the total
+    /// variation is the same for every pixel.
+    pub fn without_edge_weights(mut self) -> Self {
+        self.edge_weights = None;
+        self.guide = None;
+        self
+    }
+
+    /// Returns the guide image and the `edge_scale` from which the solvers
+    /// compute the edge weights, if set with
+    /// [`with_guide`](crate::ImageArray::with_guide).
+    pub fn guide(&self) -> Option<(&T, f64)> {
+        self.guide
+            .as_ref()
+            .map(|(guide, edge_scale)| (guide.as_ref(), *edge_scale))
+    }
+
+    /// Sets the guide image from which the solvers compute the edge
+    /// weights, replacing the edge weights, if any. The guide and
+    /// `edge_scale` are checked by
+    /// [`with_guide`](crate::ImageArray::with_guide).
+    pub(crate) fn with_guide(mut self, guide: T, edge_scale: f64) -> Self {
+        self.guide = Some((Arc::new(guide), edge_scale));
+        self.edge_weights = None;
+        self
+    }
+
+    /// Returns how the solvers handle the last channel as alpha, or `None`
+    /// if the image has no alpha channel.
+    pub fn alpha_policy(&self) -> Option<AlphaPolicy> {
+        self.alpha_policy
+    }
+
+    /// Sets how the solvers handle the alpha channel, i.e. the last channel
+    /// of the image, see
+    /// [`with_alpha_policy`](crate::ImageArray::with_alpha_policy).
+    pub fn with_alpha_policy(mut self, alpha_policy: AlphaPolicy) -> Self {
+        self.alpha_policy = Some(alpha_policy);
+        self
+    }
+
+    /// Removes the alpha policy, so that the last channel is handled as any
+    /// other channel.
+    pub fn without_alpha_policy(mut self) -> Self {
+        self.alpha_policy = None;
+        self
+    }
+
+    /// Returns the mask of the observed values of the image, if any.
+    pub(crate) fn observation_mask(&self) -> Option<&T> {
+        self.observation_mask.as_deref()
+    }
+
+    /// Sets the mask of the observed values of the image, which is 1 where
+    /// the value of the image is observed and 0 where it is not, e.g. for
+    /// the colors which are not captured through a color filter array. It
+    /// multiplies the lambda map, i.e. the data fidelity term is removed for
+    /// the unobserved values, which are filled in by the total variation.
+    /// Unlike the lambda map it is not checked for positive values, but
+    /// only the solvers with a proximal step of the data fidelity term
+    /// ([`denoise`](crate::ImageArray::denoise) and
+    /// [`denoise_adaptive`](crate::ImageArray::denoise_adaptive)) support
+    /// unobserved values, the others return an error.
+    pub(crate) fn with_observation_mask(mut self, mask: T) -> Self {
+        self.observation_mask = Some(Arc::new(mask));
+        self
+    }
+
+    /// Same options, with the lambda map, edge weights and observation mask
+    /// (if any) mapped with `f`, e.g. for slicing them along with the image.
+    pub(crate) fn map_arrays(&self, f: impl Fn(&T) -> T) -> Self {
+        let map = |x: &Option<Arc<T>>| x.as_deref().map(|x| Arc::new(f(x)));
+        Self {
+            lambda_map: map(&self.lambda_map),
+            edge_weights: map(&self.edge_weights),
+            observation_mask: map(&self.observation_mask),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::{
+        s,
+        Array3,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{
+        AlphaPolicy,
+        SolverOptions,
+        TotalVariation,
+    };
+    use crate::ops::Boundary;
+
+    #[test]
+    fn solver_options_default() {
+        let options = SolverOptions::<Array3<f64>>::default();
+
+        assert_eq!(options.boundary(), Boundary::Neumann);
+        assert_eq!(options.total_variation(), TotalVariation::Isotropic);
+        assert_eq!(options.lambda_map(), None);
+        assert_eq!(options.edge_weights(), None);
+        assert_eq!(options.alpha_policy(), None);
+    }
+
+    #[test]
+    fn solver_options_clone_shares_arrays() {
+        let options = SolverOptions::default()
+            .with_lambda_map(Array3::<f64>::ones((10, 5, 1)))
+            .with_edge_weights(Array3::<f64>::ones((10, 5, 1)));
+
+        let cloned = options.clone();
+
+        assert!(std::ptr::eq(
+            options.lambda_map().unwrap(),
+            cloned.lambda_map().unwrap()
+        ));
+        assert!(std::ptr::eq(
+            options.edge_weights().unwrap(),
+            cloned.edge_weights().unwrap()
+        ));
+    }
+
+    #[test]
+    fn solver_options_map_arrays() {
+        let options = SolverOptions::default()
+            .with_boundary(Boundary::Periodic)
+            .with_lambda_map(Array3::<f64>::ones((10, 5, 4)))
+            .with_alpha_policy(AlphaPolicy::Separate);
+
+        let mapped =
+            options.map_arrays(|x| x.slice(s![.., .., ..3]).to_owned());
+
+        assert_eq!(mapped.boundary(), Boundary::Periodic);
+        assert_eq!(mapped.alpha_policy(), Some(AlphaPolicy::Separate));
+        assert_eq!(mapped.lambda_map().unwrap().dim(), (10, 5, 3));
+        assert_eq!(mapped.edge_weights(), None);
+    }
+}
@@ -0,0 +1,323 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Reading and writing of images in the Portable Float Map (PFM) format,
+//! which stores the values as 32-bit floats, so that intermediate results
+//! can be stored without quantization.
+use std::{
+    fs::File,
+    io::{
+        self,
+        BufRead,
+        BufReader,
+        BufWriter,
+        Read,
+        Write,
+    },
+    path::Path,
+};
+
+use ndarray::{
+    Array3,
+    Axis,
+    ShapeBuilder,
+};
+
+use crate::image_array::ImageArray;
+
+impl ImageArray<Array3<f64>> {
+    /// Writes the image in the PFM format, with 1 channel (`Pf`, gray) or 3
+    /// channels (`PF`, color), and little-endian values. The values of the
+    /// pixels are written, i.e. the
+    /// [normalization](crate::ImageArray::normalization) is undone and the
+    /// colors are converted into RGB, as for the conversions into images.
+    /// They are converted to `f32` as they are otherwise, i.e. they are not
+    /// scaled into `0.0..=1.0`. Returns an error of kind `InvalidInput` if the
+    /// image does not have 1 or 3 channels, or any error from writing.
+    pub fn write_pfm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let (width, height, len_z) = self.dim();
+        let magic = match len_z {
+            1 => "Pf",
+            3 => "PF",
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "PFM images must have 1 or 3 channels",
+                ))
+            },
+        };
+
+        let pixels = self.to_pixel_values();
+        // a negative scale means little-endian values
+        write!(writer, "{}\n{} {}\n-1.0\n", magic, width, height)?;
+        // the rows are stored from the bottom to the top of the image
+        for y in (0..height).rev() {
+            for x in 0..width {
+                for z in 0..len_z {
+                    let value = pixels[[x, y, z]] as f32;
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    /// Reads an image in the PFM format, with 1 channel (`Pf`) or 3
+    /// channels (`PF`), and values of either endianness. The values are
+    /// read as they are, i.e. the magnitude of the scale in the header is
+    /// ignored. The array is stored with the
+    /// [`Layout::Planar`](crate::Layout::Planar) memory layout. Returns an
+    /// error of kind `InvalidData` if the header is not valid (including a
+    /// size which does not fit in memory), of kind `UnexpectedEof` if there
+    /// are less values than given by the header, or any error from reading.
+    /// Memory is only allocated for the values which are actually read.
+    pub fn read_pfm<R: BufRead>(mut reader: R) -> io::Result<Self> {
+        let len_z = match read_token(&mut reader)?.as_str() {
+            "Pf" => 1,
+            "PF" => 3,
+            _ => return Err(invalid_data("not a PFM image")),
+        };
+        let width: usize = parse_token(&mut reader)?;
+        let height: usize = parse_token(&mut reader)?;
+        let scale: f32 = parse_token(&mut reader)?;
+        let from_bytes = if scale < 0.0 {
+            f32::from_le_bytes
+        } else {
+            f32::from_be_bytes
+        };
+
+        // the size comes from the header, so it is not trusted for
+        // allocating memory, only the data which is actually read is
+        let len = width
+            .checked_mul(height)
+            .and_then(|len| len.checked_mul(len_z))
+            .and_then(|len| len.checked_mul(4))
+            .ok_or_else(|| invalid_data("PFM image size is too large"))?;
+        let mut bytes = Vec::new();
+        reader.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let values = bytes
+            .chunks_exact(4)
+            .map(|chunk| from_bytes(chunk.try_into().unwrap()) as f64)
+            .collect();
+        // the rows are stored from the bottom to the top of the image
+        let mut rows =
+            Array3::from_shape_vec((height, width, len_z), values)
+                .map_err(|_| invalid_data("PFM image size is too large"))?;
+        rows.invert_axis(Axis(0));
+
+        let mut array = Array3::<f64>::zeros((width, height, len_z).f());
+        array.assign(&rows.permuted_axes([1, 0, 2]));
+
+        Ok(Self::from(array))
+    }
+
+    /// Saves the image as a PFM file at the given path, see
+    /// [`write_pfm`](Self::write_pfm).
+    pub fn save_pfm<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_pfm(BufWriter::new(File::create(path)?))
+    }
+
+    /// Loads an image from a PFM file at the given path, see
+    /// [`read_pfm`](Self::read_pfm).
+    pub fn load_pfm<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_pfm(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Reads a whitespace separated token of the header, and the single
+/// whitespace character following it.
+fn read_token<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut token = Vec::new();
+    for byte in reader.by_ref().bytes() {
+        let byte = byte?;
+        if !byte.is_ascii_whitespace() {
+            token.push(byte);
+        } else if !token.is_empty() {
+            break;
+        }
+    }
+
+    String::from_utf8(token).map_err(|_| invalid_data("invalid PFM header"))
+}
+
+/// Reads and parses a token of the header.
+fn parse_token<R: BufRead, T: std::str::FromStr>(
+    reader: &mut R,
+) -> io::Result<T> {
+    read_token(reader)?
+        .parse()
+        .map_err(|_| invalid_data("invalid PFM header"))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use ndarray::Array3;
+    use pretty_assertions::assert_eq;
+
+    use crate::{
+        image_array::ImageArray,
+        ColorSpace,
+        Layout,
+        Normalization,
+    };
+
+    fn random_image_array(
+        shape: (usize, usize, usize),
+    ) -> ImageArray<Array3<f64>> {
+        let mut array = Array3::<f64>::zeros(shape);
+        // values which are exact as f32, outside of 0.0..=1.0
+        array.mapv_inplace(|_| (rand::random::<f32>() * 1000.0 - 10.0) as f64);
+        ImageArray::from(array)
+    }
+
+    #[test]
+    fn write_pfm_and_read_pfm() {
+        for len_z in [1, 3] {
+            let image_array = random_image_array((10, 5, len_z));
+
+            let mut bytes = Vec::new();
+            image_array.write_pfm(&mut bytes).unwrap();
+            let read_image_array = ImageArray::read_pfm(&bytes[..]).unwrap();
+
+            assert_eq!(*read_image_array, *image_array);
+            assert_eq!(read_image_array.layout(), Some(Layout::Planar));
+        }
+    }
+
+    #[test]
+    fn write_pfm_header_and_bottom_to_top_rows() {
+        let mut array = Array3::<f64>::zeros((2, 3, 1));
+        array[[1, 2, 0]] = 0.5;
+        let image_array = ImageArray::from(array);
+
+        let mut bytes = Vec::new();
+        image_array.write_pfm(&mut bytes).unwrap();
+
+        let header = b"Pf\n2 3\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 6 * 4);
+        // the last row of the image is the first row of the file
+        let value = &bytes[header.len() + 4..header.len() + 8];
+        assert_eq!(value, 0.5_f32.to_le_bytes());
+    }
+
+    #[test]
+    fn write_pfm_undoes_normalization() {
+        let image_array =
+            ImageArray::from(Array3::<f64>::from_elem((10, 5, 3), 200.0))
+                .to_normalized(Normalization::UnitVariance)
+                .unwrap();
+
+        let mut bytes = Vec::new();
+        image_array.write_pfm(&mut bytes).unwrap();
+        let read_image_array = ImageArray::read_pfm(&bytes[..]).unwrap();
+
+        assert!(read_image_array.iter().all(|&x| (x - 200.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn write_pfm_converts_colors_into_rgb() {
+        let image_array =
+            ImageArray::from(Array3::<f64>::from_elem((10, 5, 3), 200.0))
+                .to_color_space(ColorSpace::Lab)
+                .unwrap();
+
+        let mut bytes = Vec::new();
+        image_array.write_pfm(&mut bytes).unwrap();
+        let read_image_array = ImageArray::read_pfm(&bytes[..]).unwrap();
+
+        assert!(read_image_array.iter().all(|&x| (x - 200.0).abs() < 1e-4));
+    }
+
+    #[test]
+    fn read_pfm_big_endian() {
+        let mut bytes = b"PF 1 2 1.0\n".to_vec();
+        for value in [1.0_f32, 2.0, 3.0, 4.0, 5.0, 6.0] {
+            bytes.extend(value.to_be_bytes());
+        }
+
+        let image_array = ImageArray::read_pfm(&bytes[..]).unwrap();
+
+        let test_array = Array3::from_shape_vec(
+            (1, 2, 3),
+            vec![4.0, 5.0, 6.0, 1.0, 2.0, 3.0],
+        )
+        .unwrap();
+        assert_eq!(*image_array, test_array);
+    }
+
+    #[test]
+    fn read_pfm_returns_error_if_image_is_invalid() {
+        let not_pfm = ImageArray::read_pfm(&b"P6\n1 1\n255\n"[..]);
+        let invalid_header = ImageArray::read_pfm(&b"PF\n1 x\n-1.0\n"[..]);
+        let truncated = ImageArray::read_pfm(&b"Pf\n1 1\n-1.0\n\0\0"[..]);
+
+        assert_eq!(not_pfm.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            invalid_header.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(truncated.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_pfm_returns_error_if_size_is_too_large() {
+        let too_large =
+            ImageArray::read_pfm(&b"PF 100000000000 100000000000 -1.0\n"[..]);
+        let empty_too_large =
+            ImageArray::read_pfm(&b"PF 0 18446744073709551615 -1.0\n"[..]);
+        // a size which fits in memory, but is larger than the data
+        let truncated = ImageArray::read_pfm(&b"PF 100000 100000 -1.0\n"[..]);
+
+        assert_eq!(too_large.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            empty_too_large.unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(truncated.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn write_pfm_returns_error_if_channels_are_not_1_or_3() {
+        let image_array = random_image_array((10, 5, 4));
+
+        let written = image_array.write_pfm(Vec::new());
+
+        assert_eq!(written.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn save_pfm_and_load_pfm() {
+        let image_array = random_image_array((10, 5, 3));
+        let path = std::env::temp_dir()
+            .join(format!("image_recovery_test_{}.pfm", rand::random::<u64>()));
+
+        image_array.save_pfm(&path).unwrap();
+        let loaded_image_array = ImageArray::load_pfm(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(*loaded_image_array.unwrap(), *image_array);
+    }
+}
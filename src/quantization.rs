@@ -0,0 +1,266 @@
+// Copyright (C) 2022  Lílian Ferreira de Freitas & Emilia L. K. Blåsten
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Quantization of the arrays into the integer values of images.
+use image::Primitive;
+use ndarray::{
+    Array3,
+    Axis,
+};
+
+/// How the values of an `ImageArray` are converted into the integer values
+/// of an image, e.g. by [`into_rgb`](crate::ImageArray::into_rgb), see
+/// [`with_quantization`](crate::ImageArray::with_quantization). The values
+/// outside of the range of the image are always clamped to the range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Quantization {
+    /// Each value is rounded to the nearest integer.
+    #[default]
+    Round,
+    /// Each value is rounded to the nearest integer, and the number of
+    /// values clamped to the range of the image is reported with
+    /// `log::warn!`, if any. The number is also returned by the conversions
+    /// with a report (for every quantization), e.g.
+    /// [`into_rgb_with_report`](crate::ImageArray::into_rgb_with_report).
+    ClampAndReport,
+    /// Error-diffusion dithering (Floyd–Steinberg) on each channel, i.e. the
+    /// rounding error of each pixel is spread to the pixels to its right
+    /// and below, which avoids banding on smooth gradients. The values
+    /// outside of the range are clamped before, so only the rounding error
+    /// is spread, and their number is reported as for
+    /// [`ClampAndReport`](Self::ClampAndReport).
+    Dither,
+    /// Each value is truncated toward zero, i.e. the conversion of previous
+    /// versions, which darkens the image by half a level on average.
+    Truncate,
+}
+
+/// Number of values outside of the range of an image, see
+/// [`clipped_count`](crate::ImageArray::clipped_count) and
+/// [`into_rgb_with_report`](crate::ImageArray::into_rgb_with_report).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClippedCount {
+    /// Number of values below 0.
+    pub below: usize,
+    /// Number of values above the maximum value of the image.
+    pub above: usize,
+}
+
+impl ClippedCount {
+    /// Counts the values of the array outside of `0.0..=max`.
+    pub(crate) fn of(array: &Array3<f64>, max: f64) -> Self {
+        array.fold(Self::default(), |mut count, &x| {
+            count.below += (x < 0.0) as usize;
+            count.above += (x > max) as usize;
+            count
+        })
+    }
+
+    /// Total number of values outside of the range.
+    pub fn total(&self) -> usize {
+        self.below + self.above
+    }
+}
+
+/// Types of the channels of the images the arrays are converted into.
+pub(crate) trait Subpixel: Primitive + Into<f64> {
+    /// Maximum value of the integer types, which are quantized, or `None`
+    /// for the float types, which are not.
+    const QUANTIZATION_MAX: Option<f64>;
+
+    /// Converts the (already quantized) value.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Subpixel for u8 {
+    const QUANTIZATION_MAX: Option<f64> = Some(u8::MAX as f64);
+
+    fn from_f64(value: f64) -> Self {
+        value as u8
+    }
+}
+
+impl Subpixel for u16 {
+    const QUANTIZATION_MAX: Option<f64> = Some(u16::MAX as f64);
+
+    fn from_f64(value: f64) -> Self {
+        value as u16
+    }
+}
+
+impl Subpixel for f32 {
+    const QUANTIZATION_MAX: Option<f64> = None;
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+/// Quantizes the channels (axis 2) of an image to the integers in
+/// `0.0..=max`, in place. Returns the number of values outside of the range
+/// before quantization, which are clamped to it.
+pub(crate) fn quantize(
+    channels: &mut Array3<f64>,
+    quantization: Quantization,
+    max: f64,
+) -> ClippedCount {
+    let clipped = ClippedCount::of(channels, max);
+    match quantization {
+        Quantization::Round => {
+            channels.mapv_inplace(|x| x.round().clamp(0.0, max))
+        },
+        Quantization::ClampAndReport => {
+            warn_clipped(clipped, max);
+            channels.mapv_inplace(|x| x.round().clamp(0.0, max))
+        },
+        Quantization::Dither => {
+            warn_clipped(clipped, max);
+            dither(channels, max)
+        },
+        Quantization::Truncate => {
+            channels.mapv_inplace(|x| x.trunc().clamp(0.0, max))
+        },
+    }
+
+    clipped
+}
+
+/// Reports the number of clipped values with `log::warn!`, if any.
+fn warn_clipped(clipped: ClippedCount, max: f64) {
+    if clipped.total() > 0 {
+        log::warn!(
+            "clipped {} values below 0 and {} values above {}",
+            clipped.below,
+            clipped.above,
+            max
+        );
+    }
+}
+
+/// Floyd–Steinberg dithering of each channel, scanning the rows (y axis)
+/// from left to right (x axis). Each value (with the error spread to it) is
+/// clamped to `0.0..=max` before rounding, and only the rounding error is
+/// spread, since spreading the clamping error would shift the pixels next
+/// to saturated areas.
+fn dither(channels: &mut Array3<f64>, max: f64) {
+    let (width, height, _) = channels.dim();
+    for mut channel in channels.axis_iter_mut(Axis(2)) {
+        for y in 0..height {
+            for x in 0..width {
+                let target = channel[[x, y]].clamp(0.0, max);
+                let quantized = target.round();
+                let error = target - quantized;
+                channel[[x, y]] = quantized;
+
+                if x + 1 < width {
+                    channel[[x + 1, y]] += error * 7.0 / 16.0;
+                }
+                if y + 1 < height {
+                    if x > 0 {
+                        channel[[x - 1, y + 1]] += error * 3.0 / 16.0;
+                    }
+                    channel[[x, y + 1]] += error * 5.0 / 16.0;
+                    if x + 1 < width {
+                        channel[[x + 1, y + 1]] += error * 1.0 / 16.0;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ndarray::Array3;
+    use pretty_assertions::assert_eq;
+
+    use super::{
+        quantize,
+        ClippedCount,
+        Quantization,
+    };
+
+    #[test]
+    fn quantize_round_and_truncate() {
+        let array = Array3::from_shape_vec(
+            (3, 2, 1),
+            vec![-3.0, 0.4, 0.6, 254.5, 255.2, 300.0],
+        )
+        .unwrap();
+
+        let mut rounded = array.clone();
+        let clipped = quantize(&mut rounded, Quantization::Round, 255.0);
+        let mut truncated = array;
+        quantize(&mut truncated, Quantization::Truncate, 255.0);
+
+        assert_eq!(clipped, ClippedCount { below: 1, above: 2 });
+        assert_eq!(
+            rounded.into_raw_vec(),
+            vec![0.0, 0.0, 1.0, 255.0, 255.0, 255.0]
+        );
+        assert_eq!(
+            truncated.into_raw_vec(),
+            vec![0.0, 0.0, 0.0, 254.0, 255.0, 255.0]
+        );
+    }
+
+    #[test]
+    fn quantize_dither_keeps_mean() {
+        // flat area between two levels, which rounding would darken
+        let array = Array3::<f64>::from_elem((64, 64, 2), 100.3);
+        let mean = array.mean().unwrap();
+
+        let mut dithered = array.clone();
+        quantize(&mut dithered, Quantization::Dither, 255.0);
+        let mut rounded = array;
+        quantize(&mut rounded, Quantization::Round, 255.0);
+
+        assert!(dithered.iter().all(|&x| x == 100.0 || x == 101.0));
+        assert!((dithered.mean().unwrap() - mean).abs() < 1e-2);
+        assert!((rounded.mean().unwrap() - mean).abs() > 1e-1);
+    }
+
+    #[test]
+    fn quantize_dither_spreads_only_rounding_error() {
+        let array = Array3::from_shape_vec(
+            (4, 2, 1),
+            vec![300.0, -50.0, 100.0, 100.0, 100.0, 100.0, 100.0, 100.0],
+        )
+        .unwrap();
+
+        let mut dithered = array.clone();
+        let clipped = quantize(&mut dithered, Quantization::Dither, 255.0);
+
+        assert_eq!(clipped, ClippedCount { below: 1, above: 1 });
+        assert_eq!(
+            dithered.into_raw_vec(),
+            vec![255.0, 0.0, 100.0, 100.0, 100.0, 100.0, 100.0, 100.0]
+        );
+    }
+
+    #[test]
+    fn clipped_count() {
+        let array = Array3::from_shape_vec(
+            (3, 2, 1),
+            vec![-3.0, 0.0, 0.6, 255.0, 255.2, 300.0],
+        )
+        .unwrap();
+
+        let clipped = ClippedCount::of(&array, 255.0);
+
+        assert_eq!(clipped, ClippedCount { below: 1, above: 2 });
+        assert_eq!(clipped.total(), 3);
+    }
+}
@@ -14,14 +14,23 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! Implementation of algorithms for image recovery.
-use std::ops::Deref;
+use std::{
+    borrow::Cow,
+    ops::Deref,
+};
 
 use ndarray::{
+    s,
+    Array2,
     Array3,
+    ArrayViewMut3,
+    Axis,
     ShapeError,
+    Zip,
 };
 
 use crate::{
+    color_space::ColorSpace,
     image_array::ImageArray,
     ops::{
         Average,
@@ -29,20 +38,30 @@ use crate::{
         Norm,
         VectorLen,
     },
+    options::{
+        AlphaPolicy,
+        TotalVariation,
+    },
 };
 
 impl ImageArray<Array3<f64>> {
     /// Image denoising algorithm for 2 dimentional shapes with 1 dimention of
     /// information (pixels) as an arbitrarily sized vector. Assumes axes 0
     /// and 1 and the x and y coordinates of the image, and axis 2 is the
-    /// pixel vector coordinate of the image.
+    /// pixel vector coordinate of the image. The gradients on axes 0 and 1
+    /// use the [`Boundary`](crate::Boundary) condition of the image, see
+    /// [`with_boundary`](Self::with_boundary), and are regularized with its
+    /// [`TotalVariation`], see
+    /// [`with_total_variation`](Self::with_total_variation), weighted by the
+    /// edges of a guide image if set with [`with_guide`](Self::with_guide).
     ///
     /// # inputs
     /// `lambda` is the target value of the dual objective function,
     /// i.e. how close you want the output to be to the input:
     /// approaching 0, the output should be completely smooth (flat),
     /// approaching "infinifty", the output should be the same as
-    /// the original input.
+    /// the original input. It is scaled for each pixel by the lambda map of
+    /// the image, if set with [`with_lambda_map`](Self::with_lambda_map).
     ///
     /// `tau` and `sigma` affect how fast the algorithm converges,
     /// according to Chambolle, A. and Pock, T. (2011) these should
@@ -59,45 +78,123 @@ impl ImageArray<Array3<f64>> {
     pub fn denoise(
         &self,
         lambda: f64,
+        tau: f64,
+        sigma: f64,
+        gamma: f64,
+        max_iter: u32,
+        convergence_threshold: f64,
+    ) -> Result<Self, ShapeError> {
+        let mut denoised = self.deref().clone();
+        self.solve_with_alpha_into(
+            denoised.view_mut(),
+            |image_array, output| {
+                image_array.denoise_into(
+                    output,
+                    lambda,
+                    tau,
+                    sigma,
+                    gamma,
+                    max_iter,
+                    convergence_threshold,
+                )
+            },
+        )?;
+
+        Ok(self.with_inner(denoised))
+    }
+
+    /// Same as [`denoise`](Self::denoise), but writes the output into the
+    /// array of `self` instead of returning a new `ImageArray`. On error
+    /// `self` is left unchanged.
+    ///
+    /// The iterations run directly on the array of `self`, and the primal
+    /// and dual variables are allocated once and updated in place. The
+    /// solver still needs the input as the target of the data fidelity term
+    /// until it finishes, so a single copy of the input is made. An `f64`
+    /// array can be moved into an `ImageArray` without copying it, with
+    /// `ImageArray::from(array)`, so denoising it in place makes no other
+    /// copy.
+    pub fn denoise_in_place(
+        &mut self,
+        lambda: f64,
+        tau: f64,
+        sigma: f64,
+        gamma: f64,
+        max_iter: u32,
+        convergence_threshold: f64,
+    ) -> Result<(), ShapeError> {
+        let input = self.with_inner((**self).clone());
+        let denoised = input.solve_with_alpha_into(
+            self.inner_mut().view_mut(),
+            |image_array, output| {
+                image_array.denoise_into(
+                    output,
+                    lambda,
+                    tau,
+                    sigma,
+                    gamma,
+                    max_iter,
+                    convergence_threshold,
+                )
+            },
+        );
+        if denoised.is_err() {
+            self.inner_mut().assign(&*input);
+        }
+
+        denoised
+    }
+
+    /// Runs the iterations of [`denoise`](Self::denoise) on `current`,
+    /// which has the shape of `self` and holds the output when it returns.
+    /// The input of the data fidelity term is `self`.
+    #[allow(clippy::too_many_arguments)]
+    fn denoise_into(
+        &self,
+        mut current: ArrayViewMut3<f64>,
+        lambda: f64,
         mut tau: f64,
         mut sigma: f64,
         gamma: f64,
         max_iter: u32,
         convergence_threshold: f64,
-    ) -> Result<Self, ShapeError> {
-        // primal variable (two copies, for storing value of iteration n-1)
-        let mut current: Array3<f64> = self.deref().clone();
-        let mut previous: Array3<f64>;
+    ) -> Result<(), ShapeError> {
+        let lambda_map = self.scaled_lambda_map(lambda)?;
+        let edge_weights = self.broadcast_edge_weights()?;
+
+        // the variables are allocated once, with the memory layout of the
+        // image, and updated in place on every iteration
+        current.assign(&**self);
+        // primal variable of iteration n-1
+        let mut previous: Array3<f64> = self.deref().clone();
         // primal variable "bar"
-        let mut current_bar = current.clone();
+        let mut current_bar = previous.clone();
         // dual variables
-        let mut dual_a = current.positive_gradient_on_axis(0)?;
-        let mut dual_b = current.positive_gradient_on_axis(1)?;
+        let (mut dual_a, mut dual_b) = self.positive_gradient_of(&previous)?;
         // theta will be set upon first iteration
         let mut theta: f64;
 
         let mut iter: u32 = 1;
         loop {
             // update the dual variable
-            dual_a =
-                &dual_a + (sigma * current_bar.positive_gradient_on_axis(0)?);
-            dual_b =
-                &dual_b + (sigma * current_bar.positive_gradient_on_axis(1)?);
-            // project dual variables color axis into L2 ball (-1, 1).
-            // assumes axis 2 is color axis of image.
-            let max = dual_a
-                .vector_len_on_axis(&dual_b, 2)?
-                .map(|&x| 1_f64.max(x));
-            dual_a /= &max;
-            dual_b /= &max;
+            let (gradient_a, gradient_b) =
+                self.positive_gradient_of(&current_bar)?;
+            dual_a.scaled_add(sigma, &gradient_a);
+            dual_b.scaled_add(sigma, &gradient_b);
+            self.project_dual(&mut dual_a, &mut dual_b, edge_weights.as_ref())?;
 
             // update the primal variable
-            previous = current.clone();
-            current = &current
-                - (tau
-                    * (dual_a.negative_gradient_on_axis(0)?
-                        + dual_b.negative_gradient_on_axis(1)?));
-            current = self.weighted_average(&current, tau, lambda);
+            previous.assign(&current);
+            current.scaled_add(
+                -tau,
+                &self.negative_gradient_of(&dual_a, &dual_b)?,
+            );
+            self.fidelity_step_in_place(
+                &mut current,
+                tau,
+                lambda,
+                lambda_map.as_ref(),
+            );
 
             // update theta
             theta = 1_f64 / (1_f64 + (2_f64 * gamma * tau));
@@ -107,7 +204,156 @@ impl ImageArray<Array3<f64>> {
             sigma /= theta;
 
             // update the primal variable bar
-            current_bar = &current + &(theta * (&current - &previous));
+            Zip::from(&mut current_bar)
+                .and(&current)
+                .and(&previous)
+                .for_each(|bar, &current, &previous| {
+                    *bar = current + theta * (current - previous)
+                });
+
+            // check for convergence or max_iter iterations
+            let (change, norm) = Zip::from(&current).and(&previous).fold(
+                (0_f64, 0_f64),
+                |(change, norm), &current, &previous| {
+                    (
+                        change + (current - previous).powi(2),
+                        norm + previous.powi(2),
+                    )
+                },
+            );
+            let c = change.sqrt() / norm.sqrt();
+            if c < convergence_threshold || iter >= max_iter {
+                log::debug!(
+                    "returned at iteration = {}; where max = {}",
+                    iter,
+                    max_iter
+                );
+                log::debug!(
+                    "convergence = {}; where threshold = {}",
+                    c,
+                    convergence_threshold
+                );
+                break;
+            }
+            iter += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Image denoising algorithm with adaptive step sizes, for the same
+    /// problem solved by [`denoise`](Self::denoise). Instead of fixed
+    /// `tau`, `sigma` and `gamma` chosen from a bound on the `L2 norm`, the
+    /// step sizes are adjusted on every iteration by the linesearch of
+    /// Malitsky, Y. and Pock, T. (2018), "A first-order primal-dual algorithm
+    /// with linesearch".
+    ///
+    /// # inputs
+    /// `lambda` is the target value of the dual objective function, as in
+    /// [`denoise`](Self::denoise).
+    ///
+    /// `tau` is the initial primal step size, any positive value can be
+    /// used as it is corrected by the linesearch in the first iterations.
+    ///
+    /// `beta` is the ratio `sigma / tau` between the dual and the primal
+    /// step sizes, which is kept constant; `1.0` is a reasonable choice.
+    ///
+    /// `max_iter` and `convergence_threshold` bound the runtime of the
+    /// algorithm, i.e. it runs until `convergence_threshold < norm(current -
+    /// previous) / norm(previous)` or `max_iter` is hit.
+    pub fn denoise_adaptive(
+        &self,
+        lambda: f64,
+        tau: f64,
+        beta: f64,
+        max_iter: u32,
+        convergence_threshold: f64,
+    ) -> Result<Self, ShapeError> {
+        let denoised = self.solve_with_alpha(|image_array| {
+            image_array.denoised_array_adaptive(
+                lambda,
+                tau,
+                beta,
+                max_iter,
+                convergence_threshold,
+            )
+        })?;
+
+        Ok(self.with_inner(denoised))
+    }
+
+    fn denoised_array_adaptive(
+        &self,
+        lambda: f64,
+        mut tau: f64,
+        beta: f64,
+        max_iter: u32,
+        convergence_threshold: f64,
+    ) -> Result<Array3<f64>, ShapeError> {
+        // linesearch parameters, as chosen by Malitsky, Y. and Pock, T.
+        // (2018): tau is reduced by `mu` until the step is accepted by
+        // `delta`.
+        let mu: f64 = 0.7;
+        let delta: f64 = 0.99;
+
+        let lambda_map = self.scaled_lambda_map(lambda)?;
+        let edge_weights = self.broadcast_edge_weights()?;
+
+        // primal variable (two copies, for storing value of iteration n-1)
+        let mut current: Array3<f64> = self.deref().clone();
+        let mut previous: Array3<f64>;
+        // dual variables, and their image under the adjoint of the gradient
+        let mut dual_a = current.map(|_| 0_f64);
+        let mut dual_b = dual_a.clone();
+        let mut dual_adjoint = self.negative_gradient_of(&dual_a, &dual_b)?;
+        // ratio between the current and the previous tau
+        let mut theta: f64 = 1.0;
+
+        let mut iter: u32 = 1;
+        loop {
+            // update the primal variable
+            previous = current.clone();
+            current = &current - (tau * &dual_adjoint);
+            current =
+                self.fidelity_step(&current, tau, lambda, lambda_map.as_ref())?;
+
+            // linesearch: try to increase tau, and decrease it until the
+            // dual update is accepted
+            let previous_tau = tau;
+            tau *= (1_f64 + theta).sqrt();
+            loop {
+                theta = tau / previous_tau;
+                let sigma = beta * tau;
+
+                // update the primal variable bar
+                let current_bar = &current + &(theta * (&current - &previous));
+
+                // update the dual variable
+                let (gradient_a, gradient_b) =
+                    self.positive_gradient_of(&current_bar)?;
+                let mut next_dual_a = &dual_a + (sigma * gradient_a);
+                let mut next_dual_b = &dual_b + (sigma * gradient_b);
+                self.project_dual(
+                    &mut next_dual_a,
+                    &mut next_dual_b,
+                    edge_weights.as_ref(),
+                )?;
+                let next_dual_adjoint =
+                    self.negative_gradient_of(&next_dual_a, &next_dual_b)?;
+
+                let adjoint_change =
+                    (&next_dual_adjoint - &dual_adjoint).norm();
+                let dual_change = ((&next_dual_a - &dual_a).norm().powi(2)
+                    + (&next_dual_b - &dual_b).norm().powi(2))
+                .sqrt();
+                if beta.sqrt() * tau * adjoint_change <= delta * dual_change {
+                    dual_a = next_dual_a;
+                    dual_b = next_dual_b;
+                    dual_adjoint = next_dual_adjoint;
+                    break;
+                }
+                tau *= mu;
+            }
 
             // check for convergence or max_iter iterations
             let c = (&current - &previous).norm() / previous.norm();
@@ -122,11 +368,1577 @@ impl ImageArray<Array3<f64>> {
                     c,
                     convergence_threshold
                 );
+                log::debug!("tau = {}; sigma = {}", tau, beta * tau);
                 break;
             }
             iter += 1;
         }
 
-        Ok(ImageArray::from(&current))
+        Ok(current)
+    }
+
+    /// Image denoising algorithm for the same problem solved by
+    /// [`denoise`](Self::denoise), using an accelerated projected gradient
+    /// method (FISTA) on the dual problem of Chambolle, A. (2004), with the
+    /// adaptive restart of O'Donoghue, B. and Candès, E. (2015). The output
+    /// is recovered from the dual variables as `input - (adjoint gradient of
+    /// dual) / lambda`.
+    ///
+    /// # inputs
+    /// `lambda` is the target value of the dual objective function, as in
+    /// [`denoise`](Self::denoise). The step size of the algorithm is
+    /// `lambda / L2 norm^2`, where `L2 norm^2 <= 8`, so no step sizes need
+    /// to be chosen. With a lambda map, the smallest lambda of all pixels is
+    /// used for the step size.
+    ///
+    /// Returns an error of kind `Unsupported` if the lambda of any pixel is
+    /// 0 (e.g. for the unobserved colors of
+    /// [`demosaic`](Self::demosaic)), since the output is divided by it.
+    ///
+    /// `max_iter` and `convergence_threshold` bound the runtime of the
+    /// algorithm, i.e. it runs until `convergence_threshold < norm(current -
+    /// previous) / norm(previous)` or `max_iter` is hit.
+    pub fn denoise_fista(
+        &self,
+        lambda: f64,
+        max_iter: u32,
+        convergence_threshold: f64,
+    ) -> Result<Self, ShapeError> {
+        let denoised = self.solve_with_alpha(|image_array| {
+            image_array.denoised_array_fista(
+                lambda,
+                max_iter,
+                convergence_threshold,
+            )
+        })?;
+
+        Ok(self.with_inner(denoised))
+    }
+
+    /// Denoises the image in the given [`ColorSpace`], where the noise of
+    /// luma and chroma is less correlated than in RGB, with a separate
+    /// `lambda` for each: the first channel (luma) is denoised with
+    /// `luma_lambda`, and the other 2 colors (chroma) with `chroma_lambda`,
+    /// which can be lower since the eye is less sensitive to chroma noise.
+    /// The output is converted back into the color space of `self`, see
+    /// [`to_color_space`](Self::to_color_space).
+    ///
+    /// # inputs
+    /// `denoise` runs any of the solvers on the converted image with the
+    /// given `lambda`, which is `luma_lambda`, while `chroma_lambda` is
+    /// applied with a lambda map (multiplied by the lambda map of `self`, if
+    /// set), e.g.
+    /// `|image_array, lambda| image_array.denoise_fista(lambda, max_iter,
+    /// convergence_threshold)`. The step sizes of
+    /// [`denoise`](Self::denoise) must be chosen for the larger of the two.
+    ///
+    /// Returns an error of kind `Unsupported` if `luma_lambda` or
+    /// `chroma_lambda` is not positive and finite, an error if the image
+    /// does not have 3 colors, or any error of `denoise`.
+    pub fn denoise_in_color_space(
+        &self,
+        color_space: ColorSpace,
+        luma_lambda: f64,
+        chroma_lambda: f64,
+        denoise: impl FnOnce(&Self, f64) -> Result<Self, ShapeError>,
+    ) -> Result<Self, ShapeError> {
+        // the chroma lambda is applied relative to the luma lambda
+        let is_valid = |lambda: f64| lambda.is_finite() && lambda > 0_f64;
+        if !is_valid(luma_lambda) || !is_valid(chroma_lambda) {
+            let unsupported = ndarray::ErrorKind::Unsupported;
+            return Err(ShapeError::from_kind(unsupported));
+        }
+        let converted = self.to_color_space(color_space)?;
+
+        let len_z = self.len_of(Axis(2));
+        let mut chroma_scale = Array3::<f64>::ones((1, 1, len_z));
+        chroma_scale
+            .slice_mut(s![.., .., 1..3])
+            .fill(chroma_lambda / luma_lambda);
+        let lambda_map = match self.lambda_map() {
+            Some(lambda_map) => lambda_map * &chroma_scale,
+            None => chroma_scale,
+        };
+
+        let denoised =
+            denoise(&converted.with_lambda_map(lambda_map), luma_lambda)?
+                .to_color_space(self.color_space())?;
+
+        Ok(self.with_inner(denoised.into_inner()))
+    }
+
+    fn denoised_array_fista(
+        &self,
+        lambda: f64,
+        max_iter: u32,
+        convergence_threshold: f64,
+    ) -> Result<Array3<f64>, ShapeError> {
+        let lambda_map = self.scaled_lambda_map(lambda)?;
+        // the output is recovered by dividing by the lambda of each pixel
+        if let Some(lambda_map) = &lambda_map {
+            if lambda_map.iter().any(|&x| x <= 0_f64) {
+                let unsupported = ndarray::ErrorKind::Unsupported;
+                return Err(ShapeError::from_kind(unsupported));
+            }
+        }
+        let step = lambda_map
+            .as_ref()
+            .map_or(lambda, |map| map.fold(f64::INFINITY, |a, &b| a.min(b)))
+            / 8_f64;
+        let edge_weights = self.broadcast_edge_weights()?;
+
+        // primal variable (two copies, for storing value of iteration n-1)
+        let mut current: Array3<f64> = self.deref().clone();
+        let mut previous: Array3<f64>;
+        // dual variables
+        let mut dual_a = current.map(|_| 0_f64);
+        let mut dual_b = dual_a.clone();
+        // extrapolated dual variables, where the gradient step is taken
+        let mut extrapolated_a = dual_a.clone();
+        let mut extrapolated_b = dual_b.clone();
+        // momentum parameter
+        let mut t: f64 = 1.0;
+
+        let mut iter: u32 = 1;
+        loop {
+            // gradient step on the extrapolated dual variables, the gradient
+            // of the dual objective is the gradient of the primal variable
+            let primal = self.primal_from_dual(
+                &extrapolated_a,
+                &extrapolated_b,
+                lambda,
+                lambda_map.as_ref(),
+            )?;
+            let (gradient_a, gradient_b) =
+                self.positive_gradient_of(&primal)?;
+            let mut next_dual_a = &extrapolated_a + (step * gradient_a);
+            let mut next_dual_b = &extrapolated_b + (step * gradient_b);
+            self.project_dual(
+                &mut next_dual_a,
+                &mut next_dual_b,
+                edge_weights.as_ref(),
+            )?;
+
+            // restart the momentum if it points against the gradient step
+            let restart = ((&extrapolated_a - &next_dual_a)
+                * (&next_dual_a - &dual_a))
+                .sum()
+                + ((&extrapolated_b - &next_dual_b) * (&next_dual_b - &dual_b))
+                    .sum()
+                > 0_f64;
+            if restart {
+                t = 1.0;
+                extrapolated_a = next_dual_a.clone();
+                extrapolated_b = next_dual_b.clone();
+            } else {
+                let next_t = (1_f64 + (1_f64 + 4_f64 * t * t).sqrt()) / 2_f64;
+                let momentum = (t - 1_f64) / next_t;
+                extrapolated_a =
+                    &next_dual_a + &(momentum * (&next_dual_a - &dual_a));
+                extrapolated_b =
+                    &next_dual_b + &(momentum * (&next_dual_b - &dual_b));
+                t = next_t;
+            }
+            dual_a = next_dual_a;
+            dual_b = next_dual_b;
+
+            // update the primal variable
+            previous = current;
+            current = self.primal_from_dual(
+                &dual_a,
+                &dual_b,
+                lambda,
+                lambda_map.as_ref(),
+            )?;
+
+            // check for convergence or max_iter iterations
+            let c = (&current - &previous).norm() / previous.norm();
+            if c < convergence_threshold || iter >= max_iter {
+                log::debug!(
+                    "returned at iteration = {}; where max = {}",
+                    iter,
+                    max_iter
+                );
+                log::debug!(
+                    "convergence = {}; where threshold = {}",
+                    c,
+                    convergence_threshold
+                );
+                break;
+            }
+            iter += 1;
+        }
+
+        Ok(current)
+    }
+
+    /// Demosaicing algorithm for the raw data of a sensor captured through a
+    /// color filter array (CFA) with the given Bayer [`CfaPattern`], i.e.
+    /// `self` is an image with 1 channel, where each pixel has the value of
+    /// only one of the colors. The RGB image is reconstructed by solving
+    /// the problem of [`denoise`](Self::denoise) with 3 channels, where each
+    /// channel is only partially observed: the data fidelity term only
+    /// includes the colors observed through the CFA, and the other colors
+    /// are filled in by the total variation, which couples the channels
+    /// (see [`with_total_variation`](Self::with_total_variation)). The
+    /// problem is solved with the linesearch of
+    /// [`denoise_adaptive`](Self::denoise_adaptive), starting from the
+    /// bilinear interpolation of the observed colors.
+    ///
+    /// # inputs
+    /// `lambda` is the target value of the dual objective function, as in
+    /// [`denoise`](Self::denoise): large values keep the observed colors,
+    /// smaller values also denoise them. It is scaled for each pixel by the
+    /// lambda map of the image, if set with
+    /// [`with_lambda_map`](Self::with_lambda_map).
+    ///
+    /// `tau` and `beta` are the initial primal step size and the ratio
+    /// between the step sizes, as in
+    /// [`denoise_adaptive`](Self::denoise_adaptive).
+    ///
+    /// `max_iter` and `convergence_threshold` bound the runtime of the
+    /// algorithm, i.e. it runs until `convergence_threshold < norm(current -
+    /// previous) / norm(previous)` or `max_iter` is hit.
+    ///
+    /// Returns an error if the image does not have exactly 1 channel.
+    pub fn demosaic(
+        &self,
+        cfa_pattern: CfaPattern,
+        lambda: f64,
+        tau: f64,
+        beta: f64,
+        max_iter: u32,
+        convergence_threshold: f64,
+    ) -> Result<Self, ShapeError> {
+        let (len_x, len_y, len_z) = self.dim();
+        if len_z != 1 {
+            let incompatible = ndarray::ErrorKind::IncompatibleShape;
+            return Err(ShapeError::from_kind(incompatible));
+        }
+
+        let mask = cfa_pattern.mask(len_x, len_y);
+        let observed = &mask * self.deref();
+        // the unobserved colors are only used as initial values, since
+        // the fidelity term is removed for them
+        let image_array = self
+            .with_rgb_inner(bilinear_interpolation(&observed, &mask))
+            .with_observation_mask(mask);
+        let image_array = match self.layout() {
+            Some(layout) => image_array.to_layout(layout),
+            None => image_array,
+        };
+
+        let demosaiced = image_array.denoised_array_adaptive(
+            lambda,
+            tau,
+            beta,
+            max_iter,
+            convergence_threshold,
+        )?;
+
+        Ok(self.with_rgb_inner(demosaiced))
+    }
+
+    /// Runs the solver on the image according to its [`AlphaPolicy`], if
+    /// the last channel is alpha, or on the whole image otherwise. The
+    /// output has the same channels as `self`.
+    fn solve_with_alpha(
+        &self,
+        solve: impl Fn(&Self) -> Result<Array3<f64>, ShapeError>,
+    ) -> Result<Array3<f64>, ShapeError> {
+        // same memory layout as the image
+        let mut output = self.deref().clone();
+        self.solve_with_alpha_into(
+            output.view_mut(),
+            |image_array, mut output| {
+                output.assign(&solve(image_array)?);
+                Ok(())
+            },
+        )?;
+
+        Ok(output)
+    }
+
+    /// Same as [`solve_with_alpha`](Self::solve_with_alpha), where the
+    /// solver writes its output into the given view of the channels it runs
+    /// on, and `output` has the shape of `self` and holds the values of
+    /// `self` for the channels which are left untouched.
+    fn solve_with_alpha_into(
+        &self,
+        mut output: ArrayViewMut3<f64>,
+        solve: impl Fn(&Self, ArrayViewMut3<f64>) -> Result<(), ShapeError>,
+    ) -> Result<(), ShapeError> {
+        let Some(alpha_policy) = self.alpha_policy() else {
+            return solve(self, output);
+        };
+        let len_z = self.len_of(Axis(2));
+        if len_z < 2 {
+            let unsupported = ndarray::ErrorKind::Unsupported;
+            return Err(ShapeError::from_kind(unsupported));
+        }
+
+        let colors = self.channels(0..len_z - 1);
+        let (color_output, alpha_output) = output.multi_slice_mut((
+            s![.., .., ..len_z - 1],
+            s![.., .., len_z - 1..],
+        ));
+        match alpha_policy {
+            AlphaPolicy::Untouched => solve(&colors, color_output),
+            AlphaPolicy::Separate => {
+                solve(&colors, color_output)?;
+                solve(&self.channels(len_z - 1..len_z), alpha_output)
+            },
+            AlphaPolicy::FidelityWeight => {
+                let alpha_max = self.max_value();
+                let weights = self.slice(s![.., .., len_z - 1..]).mapv(|x| {
+                    (x / alpha_max).clamp(MIN_ALPHA_FIDELITY_WEIGHT, 1_f64)
+                });
+                let lambda_map = match colors.lambda_map() {
+                    Some(lambda_map) => lambda_map * &weights,
+                    None => weights,
+                };
+                solve(&colors.with_lambda_map(lambda_map), color_output)
+            },
+        }
+    }
+
+    /// Primal solution of the denoising problem for the given dual
+    /// variables, i.e. `self - (adjoint gradient of dual) / lambda`, where
+    /// `lambda` is the scaled lambda map if there is one.
+    fn primal_from_dual(
+        &self,
+        dual_a: &Array3<f64>,
+        dual_b: &Array3<f64>,
+        lambda: f64,
+        lambda_map: Option<&Array3<f64>>,
+    ) -> Result<Array3<f64>, ShapeError> {
+        let dual_adjoint = self.negative_gradient_of(dual_a, dual_b)?;
+
+        Ok(match lambda_map {
+            Some(lambda_map) => self.deref() - &(dual_adjoint / lambda_map),
+            None => self.deref() - &(dual_adjoint / lambda),
+        })
+    }
+
+    /// Proximal step of the data fidelity term, i.e. the weighted average of
+    /// `self` and the primal variable, with the scaled lambda map if there
+    /// is one.
+    fn fidelity_step(
+        &self,
+        current: &Array3<f64>,
+        tau: f64,
+        lambda: f64,
+        lambda_map: Option<&Array3<f64>>,
+    ) -> Result<Array3<f64>, ShapeError> {
+        match lambda_map {
+            Some(lambda_map) => {
+                self.weighted_average_with_lambda_map(current, tau, lambda_map)
+            },
+            None => Ok(self.weighted_average(current, tau, lambda)),
+        }
+    }
+
+    /// Same as [`fidelity_step`](Self::fidelity_step), updating the primal
+    /// variable in place, i.e. `(current + tau * lambda * self) / (1 + tau *
+    /// lambda)` for each element, where the lambda map has the shape of the
+    /// image.
+    fn fidelity_step_in_place(
+        &self,
+        current: &mut ArrayViewMut3<f64>,
+        tau: f64,
+        lambda: f64,
+        lambda_map: Option<&Array3<f64>>,
+    ) {
+        let step = |current: &mut f64, value: f64, lambda: f64| {
+            *current = (*current + tau * lambda * value) / (1.0 + tau * lambda)
+        };
+        match lambda_map {
+            Some(lambda_map) => {
+                Zip::from(current).and(&**self).and(lambda_map).for_each(
+                    |current, &value, &lambda| step(current, value, lambda),
+                )
+            },
+            None => Zip::from(current)
+                .and(&**self)
+                .for_each(|current, &value| step(current, value, lambda)),
+        }
+    }
+
+    /// Lambda map of `self` broadcast to the shape of the image and scaled
+    /// by `lambda`, multiplied by the observation mask (which is 0 for the
+    /// unobserved values) if there is one, or `None` if there is neither.
+    /// Returns an error if the lambda map or the mask cannot be broadcast to
+    /// the shape of the image, or if the lambda map has values which are not
+    /// positive and finite.
+    fn scaled_lambda_map(
+        &self,
+        lambda: f64,
+    ) -> Result<Option<Array3<f64>>, ShapeError> {
+        let lambda_map = self.lambda_map();
+        let mask = self.options().observation_mask();
+        if lambda_map.is_none() && mask.is_none() {
+            return Ok(None);
+        }
+        if let Some(lambda_map) = lambda_map {
+            if !lambda_map.iter().all(|x| x.is_finite() && *x > 0_f64) {
+                let unsupported = ndarray::ErrorKind::Unsupported;
+                return Err(ShapeError::from_kind(unsupported));
+            }
+        }
+
+        // same memory layout as the image
+        let mut scaled = self.deref().clone();
+        scaled.fill(lambda);
+        for array in [lambda_map, mask].into_iter().flatten() {
+            let broadcast =
+                array.broadcast(self.raw_dim()).ok_or_else(|| {
+                    ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape)
+                })?;
+            scaled *= &broadcast;
+        }
+        Ok(Some(scaled))
+    }
+
+    /// Positive gradients of the array on axes 0 and 1 (the x and y
+    /// coordinates of the image), with the boundary condition of `self`.
+    fn positive_gradient_of(
+        &self,
+        array: &Array3<f64>,
+    ) -> Result<(Array3<f64>, Array3<f64>), ShapeError> {
+        Ok((
+            array
+                .positive_gradient_on_axis_with_boundary(0, self.boundary())?,
+            array
+                .positive_gradient_on_axis_with_boundary(1, self.boundary())?,
+        ))
+    }
+
+    /// Sum of the negative gradients of the dual variables on axes 0 and 1,
+    /// with the boundary condition of `self`, which is the adjoint of
+    /// [`positive_gradient_of`](Self::positive_gradient_of).
+    fn negative_gradient_of(
+        &self,
+        dual_a: &Array3<f64>,
+        dual_b: &Array3<f64>,
+    ) -> Result<Array3<f64>, ShapeError> {
+        Ok(dual_a
+            .negative_gradient_on_axis_with_boundary(0, self.boundary())?
+            + dual_b
+                .negative_gradient_on_axis_with_boundary(1, self.boundary())?)
+    }
+
+    /// Edge weights of `self` broadcast to the shape of the image, or
+    /// computed from the guide with the current boundary condition if there
+    /// is one, or `None` if there is neither. Returns an error if the edge
+    /// weights cannot be broadcast to the shape of the image, or if they
+    /// are not positive and finite.
+    fn broadcast_edge_weights(
+        &self,
+    ) -> Result<Option<Array3<f64>>, ShapeError> {
+        let edge_weights = match (self.edge_weights(), self.options().guide()) {
+            (Some(edge_weights), _) => Cow::Borrowed(edge_weights),
+            (None, Some((guide, edge_scale))) => {
+                Cow::Owned(self.edge_weights_of_guide(guide, edge_scale)?)
+            },
+            (None, None) => return Ok(None),
+        };
+        if !edge_weights.iter().all(|x| x.is_finite() && *x > 0_f64) {
+            let unsupported = ndarray::ErrorKind::Unsupported;
+            return Err(ShapeError::from_kind(unsupported));
+        }
+        let broadcast =
+            edge_weights.broadcast(self.raw_dim()).ok_or_else(|| {
+                ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape)
+            })?;
+
+        // same memory layout as the image
+        let mut edge_weights = self.deref().clone();
+        edge_weights.assign(&broadcast);
+        Ok(Some(edge_weights))
+    }
+
+    /// Projects the dual variables into the unit ball of the dual norm of
+    /// the [`TotalVariation`] of `self`, scaled by the edge weights of each
+    /// pixel if there are any (broadcast to the shape of the dual
+    /// variables, see
+    /// [`broadcast_edge_weights`](Self::broadcast_edge_weights)).
+    /// Assumes axis 2 is color axis of image.
+    fn project_dual(
+        &self,
+        dual_a: &mut Array3<f64>,
+        dual_b: &mut Array3<f64>,
+        edge_weights: Option<&Array3<f64>>,
+    ) -> Result<(), ShapeError> {
+        // the ball of radius w is the unit ball scaled by w
+        if let Some(edge_weights) = edge_weights {
+            *dual_a /= edge_weights;
+            *dual_b /= edge_weights;
+        }
+
+        match self.total_variation() {
+            TotalVariation::Isotropic => {
+                let max = dual_a
+                    .vector_len_on_axis(dual_b, 2)?
+                    .map(|&x| 1_f64.max(x));
+                *dual_a /= &max;
+                *dual_b /= &max;
+            },
+            TotalVariation::Anisotropic => {
+                dual_a.mapv_inplace(|x| x.clamp(-1_f64, 1_f64));
+                dual_b.mapv_inplace(|x| x.clamp(-1_f64, 1_f64));
+            },
+            TotalVariation::ChannelByChannel => {
+                let max = ((&*dual_a * &*dual_a) + (&*dual_b * &*dual_b))
+                    .mapv(|x| 1_f64.max(x.sqrt()));
+                *dual_a /= &max;
+                *dual_b /= &max;
+            },
+            TotalVariation::Nuclear => project_spectral(dual_a, dual_b),
+        }
+
+        if let Some(edge_weights) = edge_weights {
+            *dual_a *= edge_weights;
+            *dual_b *= edge_weights;
+        }
+
+        Ok(())
+    }
+}
+
+/// Projects the dual variables into the unit ball of the spectral norm,
+/// where each pixel is the matrix `J = [dual_a, dual_b]` with one row per
+/// color (axis 2). Its singular values are clamped to 1 by computing `J * W`,
+/// where `W = V * diag(min(1, 1 / s)) * V^T` for the eigenvectors `V` and
+/// eigenvalues `s^2` of the 2x2 matrix `J^T * J`.
+fn project_spectral(dual_a: &mut Array3<f64>, dual_b: &mut Array3<f64>) {
+    let aa = (&*dual_a * &*dual_a).sum_axis(Axis(2));
+    let ab = (&*dual_a * &*dual_b).sum_axis(Axis(2));
+    let bb = (&*dual_b * &*dual_b).sum_axis(Axis(2));
+
+    let mut w_aa = Array2::<f64>::zeros(aa.raw_dim());
+    let mut w_ab = Array2::<f64>::zeros(aa.raw_dim());
+    let mut w_bb = Array2::<f64>::zeros(aa.raw_dim());
+    Zip::from(&mut w_aa)
+        .and(&mut w_ab)
+        .and(&mut w_bb)
+        .and(&aa)
+        .and(&ab)
+        .and(&bb)
+        .for_each(|w_aa, w_ab, w_bb, &aa, &ab, &bb| {
+            // eigenvalues of [[aa, ab], [ab, bb]]
+            let mean = (aa + bb) / 2_f64;
+            let deviation = (((aa - bb) / 2_f64).powi(2) + ab * ab).sqrt();
+            let largest = mean + deviation;
+            let smallest = (mean - deviation).max(0_f64);
+            // unit eigenvector of the largest eigenvalue
+            let (x, y) = if ab != 0_f64 {
+                (largest - bb, ab)
+            } else if aa >= bb {
+                (1_f64, 0_f64)
+            } else {
+                (0_f64, 1_f64)
+            };
+            let len = (x * x + y * y).sqrt();
+            let (x, y) = (x / len, y / len);
+
+            let scale = |eigenvalue: f64| 1_f64.min(1_f64 / eigenvalue.sqrt());
+            let (scale_largest, scale_smallest) =
+                (scale(largest), scale(smallest));
+            // the eigenvector of the smallest eigenvalue is (-y, x)
+            *w_aa = scale_largest * x * x + scale_smallest * y * y;
+            *w_ab = (scale_largest - scale_smallest) * x * y;
+            *w_bb = scale_largest * y * y + scale_smallest * x * x;
+        });
+    let w_aa = w_aa.insert_axis(Axis(2));
+    let w_ab = w_ab.insert_axis(Axis(2));
+    let w_bb = w_bb.insert_axis(Axis(2));
+
+    let projected_a = (&*dual_a * &w_aa) + (&*dual_b * &w_ab);
+    let projected_b = (&*dual_a * &w_ab) + (&*dual_b * &w_bb);
+    *dual_a = projected_a;
+    *dual_b = projected_b;
+}
+
+/// Bilinear interpolation of the observed values of each channel (axis 2),
+/// i.e. the average of the observed values in the 3x3 neighbourhood of each
+/// pixel weighted by `[1, 2, 1]` on each axis, where `mask` is 1 for the
+/// observed values and 0 otherwise. The observed values are kept, and the
+/// pixels without observed neighbours are 0.
+fn bilinear_interpolation(
+    observed: &Array3<f64>,
+    mask: &Array3<f64>,
+) -> Array3<f64> {
+    let (len_x, len_y, _) = observed.dim();
+    // ranges of the neighbours (at the offset) and of the pixels which have
+    // a neighbour at the offset, on an axis of the given len
+    let ranges = |offset: isize, len: usize| {
+        let start = (-offset).max(0) as usize;
+        let end = (len as isize - offset.max(0)).max(0) as usize;
+        let shift = |i: usize| (i as isize + offset) as usize;
+        (shift(start)..shift(end.max(start)), start..end.max(start))
+    };
+
+    let mut sum = Array3::<f64>::zeros(observed.raw_dim());
+    let mut weight = Array3::<f64>::zeros(observed.raw_dim());
+    for offset_x in -1_isize..=1 {
+        for offset_y in -1_isize..=1 {
+            let kernel = ((2 - offset_x.abs()) * (2 - offset_y.abs())) as f64;
+            let (source_x, target_x) = ranges(offset_x, len_x);
+            let (source_y, target_y) = ranges(offset_y, len_y);
+            let source = s![source_x, source_y, ..];
+            let target = s![target_x, target_y, ..];
+
+            sum.slice_mut(target)
+                .scaled_add(kernel, &observed.slice(source));
+            weight
+                .slice_mut(target)
+                .scaled_add(kernel, &mask.slice(source));
+        }
+    }
+
+    Zip::from(&mut sum)
+        .and(&weight)
+        .and(observed)
+        .and(mask)
+        .for_each(|sum, &weight, &observed, &mask| {
+            *sum = if mask > 0_f64 {
+                observed
+            } else if weight > 0_f64 {
+                *sum / weight
+            } else {
+                0_f64
+            };
+        });
+    sum
+}
+
+/// Smallest weight of the fidelity term for [`AlphaPolicy::FidelityWeight`],
+/// so that fully transparent pixels do not make the problem degenerate.
+const MIN_ALPHA_FIDELITY_WEIGHT: f64 = 1e-3;
+
+/// Bayer pattern of a color filter array (CFA), named after the colors of
+/// the 2x2 block of pixels at the top left of the image in reading order,
+/// which is repeated over the whole image, see
+/// [`demosaic`](ImageArray::demosaic).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CfaPattern {
+    /// Red, green on the first row, and green, blue on the second row.
+    Rggb,
+    /// Blue, green on the first row, and green, red on the second row.
+    Bggr,
+    /// Green, red on the first row, and blue, green on the second row.
+    Grbg,
+    /// Green, blue on the first row, and red, green on the second row.
+    Gbrg,
+}
+
+impl CfaPattern {
+    /// Color channel (0 for red, 1 for green, 2 for blue) observed at the
+    /// given pixel.
+    pub fn channel_at(&self, x: usize, y: usize) -> usize {
+        let block = match self {
+            Self::Rggb => [0, 1, 1, 2],
+            Self::Bggr => [2, 1, 1, 0],
+            Self::Grbg => [1, 0, 2, 1],
+            Self::Gbrg => [1, 2, 0, 1],
+        };
+        block[2 * (y % 2) + x % 2]
+    }
+
+    /// Array of shape `(len_x, len_y, 3)` which is 1 for the colors
+    /// observed at each pixel and 0 otherwise.
+    fn mask(&self, len_x: usize, len_y: usize) -> Array3<f64> {
+        Array3::from_shape_fn((len_x, len_y, 3), |(x, y, z)| {
+            (self.channel_at(x, y) == z) as u8 as f64
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{
+        ColorType,
+        DynamicImage,
+        ImageBuffer,
+        Luma,
+    };
+    use ndarray::{
+        s,
+        Array3,
+        ShapeError,
+    };
+    use pretty_assertions::assert_eq;
+
+    use super::{
+        bilinear_interpolation,
+        CfaPattern,
+    };
+    use crate::{
+        color_space::ColorSpace,
+        image_array::{
+            ImageArray,
+            Layout,
+        },
+        ops::{
+            Boundary,
+            Norm,
+        },
+        options::{
+            AlphaPolicy,
+            TotalVariation,
+        },
+    };
+
+    #[test]
+    fn denoise_in_place_is_same_as_denoise() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let image_array = ImageArray::from(&test_array);
+
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let sigma: f64 = 1_f64 / (8.0 * tau);
+        let lambda: f64 = 0.0259624705;
+        let gamma: f64 = 0.35 * lambda;
+
+        let denoised = image_array
+            .denoise(lambda, tau, sigma, gamma, 10, 0.0)
+            .unwrap();
+        let mut denoised_in_place = image_array;
+        denoised_in_place
+            .denoise_in_place(lambda, tau, sigma, gamma, 10, 0.0)
+            .unwrap();
+
+        assert_eq!(*denoised_in_place, *denoised);
+    }
+
+    #[test]
+    fn denoise_in_place_is_same_as_denoise_for_all_alpha_policies() {
+        let image_array = ImageArray::from(random_rgba_array((10, 5)));
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let sigma: f64 = 1_f64 / (8.0 * tau);
+        let lambda: f64 = 0.0259624705;
+
+        for alpha_policy in [
+            AlphaPolicy::Untouched,
+            AlphaPolicy::Separate,
+            AlphaPolicy::FidelityWeight,
+        ] {
+            let image_array =
+                image_array.clone().with_alpha_policy(alpha_policy);
+
+            let denoised = image_array
+                .denoise(lambda, tau, sigma, 0.0, 10, 0.0)
+                .unwrap();
+            let mut denoised_in_place = image_array;
+            denoised_in_place
+                .denoise_in_place(lambda, tau, sigma, 0.0, 10, 0.0)
+                .unwrap();
+
+            assert_eq!(*denoised_in_place, *denoised);
+        }
+    }
+
+    #[test]
+    fn denoise_in_place_leaves_image_unchanged_on_error() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let mut image_array = ImageArray::from(&test_array)
+            .with_lambda_map(Array3::<f64>::zeros((10, 5, 1)));
+
+        let denoised =
+            image_array.denoise_in_place(0.1, 0.5, 0.25, 0.0, 10, 0.0);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        assert_eq!(denoised, Err(unsupported_error));
+        assert_eq!(*image_array, test_array);
+    }
+
+    #[test]
+    fn denoise_keeps_boundary() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+
+        for boundary in
+            [Boundary::Periodic, Boundary::Neumann, Boundary::Dirichlet]
+        {
+            let image_array =
+                ImageArray::from(&test_array).with_boundary(boundary);
+
+            let denoised = image_array
+                .denoise(0.0259624705, 0.5, 0.25, 0.009, 10, 0.0)
+                .unwrap();
+            let denoised_adaptive = image_array
+                .denoise_adaptive(0.0259624705, 1.0, 1.0, 10, 0.0)
+                .unwrap();
+            let denoised_fista =
+                image_array.denoise_fista(0.0259624705, 10, 0.0).unwrap();
+
+            assert_eq!(denoised.boundary(), boundary);
+            assert_eq!(denoised_adaptive.boundary(), boundary);
+            assert_eq!(denoised_fista.boundary(), boundary);
+        }
+    }
+
+    #[test]
+    fn denoise_adaptive_converges_to_same_output_as_denoise() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let image_array = ImageArray::from(&test_array);
+
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let sigma: f64 = 1_f64 / (8.0 * tau);
+        let lambda: f64 = 0.0259624705;
+        // with acceleration tau shrinks so fast that `denoise` only gets
+        // close to the solution, without it the fixed step sizes converge
+        // to the precision of the floats
+        let gamma: f64 = 0.0;
+
+        let denoised = image_array
+            .denoise(lambda, tau, sigma, gamma, 5000, 0.0)
+            .unwrap();
+        let denoised_adaptive = image_array
+            .denoise_adaptive(lambda, 1.0, 1.0, 5000, 0.0)
+            .unwrap();
+
+        let difference = (&*denoised_adaptive - &*denoised).norm();
+        assert!(difference / denoised.norm() < 1e-6);
+    }
+
+    #[test]
+    fn denoise_fista_converges_to_same_output_as_denoise_adaptive() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let image_array = ImageArray::from(&test_array);
+
+        let lambda: f64 = 0.0259624705;
+
+        let denoised_adaptive = image_array
+            .denoise_adaptive(lambda, 1.0, 1.0, 5000, 0.0)
+            .unwrap();
+        let denoised_fista =
+            image_array.denoise_fista(lambda, 5000, 0.0).unwrap();
+
+        let difference = (&*denoised_fista - &*denoised_adaptive).norm();
+        assert!(difference / denoised_adaptive.norm() < 1e-6);
+    }
+
+    #[test]
+    fn denoise_anisotropic_converges_to_same_output_for_all_solvers() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let image_array = ImageArray::from(&test_array)
+            .with_total_variation(TotalVariation::Anisotropic);
+
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let sigma: f64 = 1_f64 / (8.0 * tau);
+        let lambda: f64 = 0.0259624705;
+
+        let denoised_isotropic = image_array
+            .clone()
+            .with_total_variation(TotalVariation::Isotropic)
+            .denoise_fista(lambda, 5000, 0.0)
+            .unwrap();
+        let denoised = image_array
+            .denoise(lambda, tau, sigma, 0.0, 5000, 0.0)
+            .unwrap();
+        let denoised_adaptive = image_array
+            .denoise_adaptive(lambda, 1.0, 1.0, 5000, 0.0)
+            .unwrap();
+        let denoised_fista =
+            image_array.denoise_fista(lambda, 5000, 0.0).unwrap();
+
+        let difference = (&*denoised_fista - &*denoised).norm();
+        assert!(difference / denoised.norm() < 1e-6);
+        let difference = (&*denoised_fista - &*denoised_adaptive).norm();
+        assert!(difference / denoised_adaptive.norm() < 1e-6);
+        let difference = (&*denoised_fista - &*denoised_isotropic).norm();
+        assert!(difference / denoised_isotropic.norm() > 1e-3);
+    }
+
+    fn random_dual(shape: (usize, usize, usize)) -> Array3<f64> {
+        let mut dual = Array3::<f64>::zeros(shape);
+        dual.mapv_inplace(|_| 4_f64 * rand::random::<f64>() - 2_f64);
+        dual
+    }
+
+    #[test]
+    fn project_dual_is_same_for_all_isotropic_total_variations_on_gray_images()
+    {
+        let image_array = ImageArray::from(&Array3::<f64>::zeros((10, 5, 1)));
+        let dual_a = random_dual((10, 5, 1));
+        let dual_b = random_dual((10, 5, 1));
+
+        let project = |total_variation| {
+            let (mut dual_a, mut dual_b) = (dual_a.clone(), dual_b.clone());
+            image_array
+                .clone()
+                .with_total_variation(total_variation)
+                .project_dual(&mut dual_a, &mut dual_b, None)
+                .unwrap();
+            (dual_a, dual_b)
+        };
+
+        let (isotropic_a, isotropic_b) = project(TotalVariation::Isotropic);
+        for total_variation in
+            [TotalVariation::ChannelByChannel, TotalVariation::Nuclear]
+        {
+            let (projected_a, projected_b) = project(total_variation);
+
+            assert!((&projected_a - &isotropic_a).norm() < 1e-12);
+            assert!((&projected_b - &isotropic_b).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn project_dual_channel_by_channel_is_isotropic_on_each_channel() {
+        let image_array = ImageArray::from(&Array3::<f64>::zeros((10, 5, 3)))
+            .with_total_variation(TotalVariation::ChannelByChannel);
+        let mut dual_a = random_dual((10, 5, 3));
+        let mut dual_b = random_dual((10, 5, 3));
+
+        let mut test_a = dual_a.clone();
+        let mut test_b = dual_b.clone();
+        let gray_image_array =
+            ImageArray::from(&Array3::<f64>::zeros((10, 5, 1)));
+        for z in 0..3 {
+            let mut channel_a = test_a.slice(s![.., .., z..z + 1]).to_owned();
+            let mut channel_b = test_b.slice(s![.., .., z..z + 1]).to_owned();
+            gray_image_array
+                .project_dual(&mut channel_a, &mut channel_b, None)
+                .unwrap();
+            test_a.slice_mut(s![.., .., z..z + 1]).assign(&channel_a);
+            test_b.slice_mut(s![.., .., z..z + 1]).assign(&channel_b);
+        }
+
+        image_array
+            .project_dual(&mut dual_a, &mut dual_b, None)
+            .unwrap();
+
+        assert_eq!(dual_a, test_a);
+        assert_eq!(dual_b, test_b);
+    }
+
+    #[test]
+    fn project_dual_nuclear_clamps_singular_values_to_1() {
+        let image_array = ImageArray::from(&Array3::<f64>::zeros((10, 5, 3)))
+            .with_total_variation(TotalVariation::Nuclear);
+        let mut dual_a = random_dual((10, 5, 3));
+        let mut dual_b = random_dual((10, 5, 3));
+        // the pixel at (0, 0) is inside of the unit ball, and is not changed
+        dual_a.slice_mut(s![0, 0, ..]).fill(0.1);
+        dual_b.slice_mut(s![0, 0, ..]).fill(-0.2);
+
+        image_array
+            .project_dual(&mut dual_a, &mut dual_b, None)
+            .unwrap();
+
+        for x in 0..10 {
+            for y in 0..5 {
+                let a = dual_a.slice(s![x, y, ..]);
+                let b = dual_b.slice(s![x, y, ..]);
+                let aa = (&a * &a).sum();
+                let ab = (&a * &b).sum();
+                let bb = (&b * &b).sum();
+                let largest_singular_value = ((aa + bb) / 2_f64
+                    + (((aa - bb) / 2_f64).powi(2) + ab * ab).sqrt())
+                .sqrt();
+
+                assert!(largest_singular_value < 1_f64 + 1e-12);
+            }
+        }
+        assert!(dual_a
+            .slice(s![0, 0, ..])
+            .iter()
+            .all(|&x| (x - 0.1).abs() < 1e-12));
+        assert!(dual_b
+            .slice(s![0, 0, ..])
+            .iter()
+            .all(|&x| (x + 0.2).abs() < 1e-12));
+    }
+
+    #[test]
+    fn denoise_nuclear_converges_to_same_output_for_all_solvers() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let image_array = ImageArray::from(&test_array)
+            .with_total_variation(TotalVariation::Nuclear);
+
+        let lambda: f64 = 0.0259624705;
+
+        let denoised_adaptive = image_array
+            .denoise_adaptive(lambda, 1.0, 1.0, 5000, 0.0)
+            .unwrap();
+        let denoised_fista =
+            image_array.denoise_fista(lambda, 5000, 0.0).unwrap();
+
+        let difference = (&*denoised_fista - &*denoised_adaptive).norm();
+        assert!(difference / denoised_adaptive.norm() < 1e-6);
+    }
+
+    #[test]
+    fn denoise_with_lambda_map_of_ones_is_same_as_without_lambda_map() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let image_array = ImageArray::from(&test_array);
+        let mapped_image_array = image_array
+            .clone()
+            .with_lambda_map(Array3::ones((10, 5, 1)));
+
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let sigma: f64 = 1_f64 / (8.0 * tau);
+        let lambda: f64 = 0.0259624705;
+        let gamma: f64 = 0.35 * lambda;
+
+        let denoised = image_array
+            .denoise(lambda, tau, sigma, gamma, 10, 0.0)
+            .unwrap();
+        let mapped_denoised = mapped_image_array
+            .denoise(lambda, tau, sigma, gamma, 10, 0.0)
+            .unwrap();
+        let denoised_fista =
+            image_array.denoise_fista(lambda, 10, 0.0).unwrap();
+        let mapped_denoised_fista =
+            mapped_image_array.denoise_fista(lambda, 10, 0.0).unwrap();
+
+        assert!((&*mapped_denoised - &*denoised).norm() < 1e-10);
+        assert!((&*mapped_denoised_fista - &*denoised_fista).norm() < 1e-10);
+        assert!(mapped_denoised.lambda_map().is_some());
+    }
+
+    #[test]
+    fn denoise_with_lambda_map_converges_to_same_output_for_all_solvers() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        // denoise the left half of the image harder than the right half
+        let mut lambda_map = Array3::<f64>::ones((10, 5, 1));
+        lambda_map.slice_mut(s![..5, .., ..]).fill(0.25);
+        let image_array =
+            ImageArray::from(&test_array).with_lambda_map(lambda_map);
+
+        let lambda: f64 = 0.0259624705;
+
+        let denoised_adaptive = image_array
+            .denoise_adaptive(lambda, 1.0, 1.0, 5000, 0.0)
+            .unwrap();
+        let denoised_fista =
+            image_array.denoise_fista(lambda, 5000, 0.0).unwrap();
+        let denoised_uniform = image_array
+            .clone()
+            .without_lambda_map()
+            .denoise_fista(lambda, 5000, 0.0)
+            .unwrap();
+
+        let difference = (&*denoised_fista - &*denoised_adaptive).norm();
+        assert!(difference / denoised_adaptive.norm() < 1e-6);
+        let difference = (&*denoised_fista - &*denoised_uniform).norm();
+        assert!(difference / denoised_uniform.norm() > 1e-6);
+    }
+
+    #[test]
+    fn denoise_returns_error_if_lambda_map_shape_is_incompatible() {
+        let test_array = Array3::<f64>::zeros((10, 5, 3));
+        let image_array = ImageArray::from(&test_array)
+            .with_lambda_map(Array3::ones((10, 5, 2)));
+
+        let lambda: f64 = 0.0259624705;
+
+        let incompatible_shape_error =
+            ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        assert_eq!(
+            image_array.denoise_fista(lambda, 10, 0.0).unwrap_err(),
+            incompatible_shape_error
+        );
+        assert_eq!(
+            image_array
+                .denoise_adaptive(lambda, 1.0, 1.0, 10, 0.0)
+                .unwrap_err(),
+            incompatible_shape_error
+        );
+    }
+
+    #[test]
+    fn denoise_returns_error_if_lambda_map_is_not_positive_and_finite() {
+        let test_array = Array3::<f64>::zeros((10, 5, 3));
+        let lambda: f64 = 0.0259624705;
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let sigma: f64 = 1_f64 / (8.0 * tau);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        for value in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let mut lambda_map = Array3::<f64>::ones((10, 5, 1));
+            lambda_map[[3, 2, 0]] = value;
+            let image_array =
+                ImageArray::from(&test_array).with_lambda_map(lambda_map);
+
+            assert_eq!(
+                image_array
+                    .denoise(lambda, tau, sigma, 0.0, 10, 0.0)
+                    .unwrap_err(),
+                unsupported_error
+            );
+            assert_eq!(
+                image_array
+                    .denoise_adaptive(lambda, 1.0, 1.0, 10, 0.0)
+                    .unwrap_err(),
+                unsupported_error
+            );
+            assert_eq!(
+                image_array.denoise_fista(lambda, 10, 0.0).unwrap_err(),
+                unsupported_error
+            );
+        }
+    }
+
+    #[test]
+    fn project_dual_with_edge_weights_clamps_to_edge_weights() {
+        let mut edge_weights = Array3::<f64>::zeros((10, 5, 1));
+        edge_weights.mapv_inplace(|_| rand::random::<f64>() + 0.1);
+
+        for total_variation in [
+            TotalVariation::Isotropic,
+            TotalVariation::Anisotropic,
+            TotalVariation::ChannelByChannel,
+            TotalVariation::Nuclear,
+        ] {
+            let image_array =
+                ImageArray::from(&Array3::<f64>::zeros((10, 5, 3)))
+                    .with_total_variation(total_variation);
+            let weighted_image_array =
+                image_array.clone().with_edge_weights(edge_weights.clone());
+
+            let mut dual_a = random_dual((10, 5, 3));
+            let mut dual_b = random_dual((10, 5, 3));
+            let mut test_dual_a = &dual_a / &edge_weights;
+            let mut test_dual_b = &dual_b / &edge_weights;
+            let broadcast_edge_weights =
+                weighted_image_array.broadcast_edge_weights().unwrap();
+            weighted_image_array
+                .project_dual(
+                    &mut dual_a,
+                    &mut dual_b,
+                    broadcast_edge_weights.as_ref(),
+                )
+                .unwrap();
+            image_array
+                .project_dual(&mut test_dual_a, &mut test_dual_b, None)
+                .unwrap();
+            test_dual_a *= &edge_weights;
+            test_dual_b *= &edge_weights;
+
+            assert!((&dual_a - &test_dual_a).norm() < 1e-10);
+            assert!((&dual_b - &test_dual_b).norm() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn denoise_returns_error_if_edge_weights_are_not_positive_and_finite() {
+        let test_array = Array3::<f64>::zeros((10, 5, 3));
+        let lambda: f64 = 0.0259624705;
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let sigma: f64 = 1_f64 / (8.0 * tau);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        for value in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let mut edge_weights = Array3::<f64>::ones((10, 5, 1));
+            edge_weights[[3, 2, 0]] = value;
+            let image_array =
+                ImageArray::from(&test_array).with_edge_weights(edge_weights);
+
+            assert_eq!(
+                image_array
+                    .denoise(lambda, tau, sigma, 0.0, 10, 0.0)
+                    .unwrap_err(),
+                unsupported_error
+            );
+            assert_eq!(
+                image_array
+                    .denoise_adaptive(lambda, 1.0, 1.0, 10, 0.0)
+                    .unwrap_err(),
+                unsupported_error
+            );
+            assert_eq!(
+                image_array.denoise_fista(lambda, 10, 0.0).unwrap_err(),
+                unsupported_error
+            );
+        }
+    }
+
+    #[test]
+    fn denoise_with_guide_keeps_edges_of_guide() {
+        // vertical edge between x = 4 and x = 5, in both the image and guide
+        let mut test_array = Array3::<f64>::zeros((10, 5, 1));
+        test_array.slice_mut(s![5.., .., ..]).fill(100.0);
+        let image_array = ImageArray::from(&test_array);
+        let guided_image_array =
+            image_array.clone().with_guide(&test_array, 10.0).unwrap();
+
+        let lambda: f64 = 0.0259624705;
+
+        let denoised = image_array.denoise_fista(lambda, 5000, 0.0).unwrap();
+        let denoised_guided =
+            guided_image_array.denoise_fista(lambda, 5000, 0.0).unwrap();
+        let denoised_guided_adaptive = guided_image_array
+            .denoise_adaptive(lambda, 1.0, 1.0, 5000, 0.0)
+            .unwrap();
+
+        let contrast =
+            |array: &Array3<f64>| array[[5, 2, 0]] - array[[4, 2, 0]];
+        assert!(contrast(&denoised_guided) > contrast(&denoised) + 1.0);
+        let difference =
+            (&*denoised_guided - &*denoised_guided_adaptive).norm();
+        assert!(difference / denoised_guided.norm() < 1e-6);
+    }
+
+    fn random_rgba_array(shape: (usize, usize)) -> Array3<f64> {
+        let mut array = Array3::<f64>::zeros((shape.0, shape.1, 4));
+        array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        array
+    }
+
+    #[test]
+    fn denoise_alpha_untouched_only_denoises_colors() {
+        let test_array = random_rgba_array((10, 5));
+        let image_array = ImageArray::from(&test_array)
+            .with_alpha_policy(AlphaPolicy::Untouched);
+        let colors_array = ImageArray::from(test_array.slice(s![.., .., ..3]));
+
+        let lambda: f64 = 0.0259624705;
+
+        let denoised = image_array.denoise_fista(lambda, 10, 0.0).unwrap();
+        let denoised_colors =
+            colors_array.denoise_fista(lambda, 10, 0.0).unwrap();
+
+        assert_eq!(denoised.slice(s![.., .., ..3]), *denoised_colors);
+        assert_eq!(
+            denoised.slice(s![.., .., 3]),
+            test_array.slice(s![.., .., 3])
+        );
+        assert_eq!(denoised.alpha_policy(), Some(AlphaPolicy::Untouched));
+    }
+
+    #[test]
+    fn denoise_alpha_separate_denoises_alpha_separately() {
+        let test_array = random_rgba_array((10, 5));
+        let image_array = ImageArray::from(&test_array)
+            .with_alpha_policy(AlphaPolicy::Separate);
+        let colors_array = ImageArray::from(test_array.slice(s![.., .., ..3]));
+        let alpha_array = ImageArray::from(test_array.slice(s![.., .., 3..]));
+
+        let tau: f64 = 1.0 / 2_f64.sqrt();
+        let sigma: f64 = 1_f64 / (8.0 * tau);
+        let lambda: f64 = 0.0259624705;
+        let gamma: f64 = 0.35 * lambda;
+
+        let denoised = image_array
+            .denoise(lambda, tau, sigma, gamma, 10, 0.0)
+            .unwrap();
+        let denoised_colors = colors_array
+            .denoise(lambda, tau, sigma, gamma, 10, 0.0)
+            .unwrap();
+        let denoised_alpha = alpha_array
+            .denoise(lambda, tau, sigma, gamma, 10, 0.0)
+            .unwrap();
+
+        assert_eq!(denoised.slice(s![.., .., ..3]), *denoised_colors);
+        assert_eq!(denoised.slice(s![.., .., 3..]), *denoised_alpha);
+    }
+
+    #[test]
+    fn denoise_alpha_fidelity_weight_of_opaque_image_only_denoises_colors() {
+        let mut test_array = random_rgba_array((10, 5));
+        test_array.slice_mut(s![.., .., 3]).fill(255.0);
+        let image_array = ImageArray::from(&test_array)
+            .with_alpha_policy(AlphaPolicy::FidelityWeight);
+        let colors_array = ImageArray::from(test_array.slice(s![.., .., ..3]));
+
+        let lambda: f64 = 0.0259624705;
+
+        let denoised = image_array
+            .denoise_adaptive(lambda, 1.0, 1.0, 10, 0.0)
+            .unwrap();
+        let denoised_colors = colors_array
+            .denoise_adaptive(lambda, 1.0, 1.0, 10, 0.0)
+            .unwrap();
+
+        assert!(
+            (&denoised.slice(s![.., .., ..3]) - &*denoised_colors).norm()
+                < 1e-10
+        );
+        assert_eq!(
+            denoised.slice(s![.., .., 3]),
+            test_array.slice(s![.., .., 3])
+        );
+    }
+
+    #[test]
+    fn denoise_alpha_fidelity_weight_fills_in_transparent_pixels() {
+        // opaque black image with one transparent white pixel
+        let mut test_array = Array3::<f64>::zeros((10, 5, 4));
+        test_array.slice_mut(s![.., .., 3]).fill(255.0);
+        test_array.slice_mut(s![4, 2, ..]).fill(255.0);
+        test_array[[4, 2, 3]] = 0.0;
+        let image_array = ImageArray::from(&test_array);
+
+        let lambda: f64 = 0.0259624705;
+
+        let denoised = image_array
+            .clone()
+            .with_alpha_policy(AlphaPolicy::Untouched)
+            .denoise_adaptive(lambda, 1.0, 1.0, 1000, 0.0)
+            .unwrap();
+        let denoised_weighted = image_array
+            .with_alpha_policy(AlphaPolicy::FidelityWeight)
+            .denoise_adaptive(lambda, 1.0, 1.0, 1000, 0.0)
+            .unwrap();
+
+        assert!(denoised_weighted[[4, 2, 0]] < 1.0);
+        assert!(denoised[[4, 2, 0]] > 10.0);
+    }
+
+    #[test]
+    fn denoise_in_opponent_color_space_with_same_lambda_is_same_as_rgb() {
+        // the isotropic total variation and the fidelity term are invariant
+        // under the orthonormal opponent transform
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let image_array = ImageArray::from(&test_array);
+        let lambda: f64 = 0.0259624705;
+
+        let denoised = image_array.denoise_fista(lambda, 5000, 0.0).unwrap();
+        let denoised_opponent = image_array
+            .denoise_in_color_space(
+                ColorSpace::Opponent,
+                lambda,
+                lambda,
+                |image_array, lambda| {
+                    image_array.denoise_fista(lambda, 5000, 0.0)
+                },
+            )
+            .unwrap();
+
+        let difference = (&*denoised_opponent - &*denoised).norm();
+        assert!(difference / denoised.norm() < 1e-6);
+        assert_eq!(denoised_opponent.color_space(), ColorSpace::Rgb);
+        assert!(denoised_opponent.lambda_map().is_none());
+    }
+
+    #[test]
+    fn denoise_in_color_space_with_lower_chroma_lambda_smooths_chroma() {
+        let mut test_array = Array3::<f64>::zeros((10, 5, 3));
+        test_array.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let image_array = ImageArray::from(&test_array);
+        let lambda: f64 = 0.1;
+
+        let denoise_ycbcr = |chroma_lambda: f64| {
+            image_array
+                .denoise_in_color_space(
+                    ColorSpace::YCbCr,
+                    lambda,
+                    chroma_lambda,
+                    |image_array, lambda| {
+                        image_array.denoise_fista(lambda, 1000, 0.0)
+                    },
+                )
+                .unwrap()
+                .to_color_space(ColorSpace::YCbCr)
+                .unwrap()
+        };
+        // total variation of the chroma channels on the x axis
+        let chroma_variation = |array: &Array3<f64>| {
+            let chroma = array.slice(s![.., .., 1..]);
+            (&chroma.slice(s![1.., .., ..]) - &chroma.slice(s![..-1, .., ..]))
+                .mapv(f64::abs)
+                .sum()
+        };
+        let same = denoise_ycbcr(lambda);
+        let lower = denoise_ycbcr(lambda / 10.0);
+
+        assert!(chroma_variation(&lower) < 0.5 * chroma_variation(&same));
+    }
+
+    #[test]
+    fn denoise_in_color_space_returns_error_if_image_is_not_rgb() {
+        let test_array = Array3::<f64>::zeros((10, 5, 1));
+        let image_array = ImageArray::from(&test_array);
+
+        let denoised = image_array.denoise_in_color_space(
+            ColorSpace::Lab,
+            0.1,
+            0.01,
+            |image_array, lambda| image_array.denoise_fista(lambda, 10, 0.0),
+        );
+
+        let incompatible_shape_error =
+            ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        assert_eq!(denoised.unwrap_err(), incompatible_shape_error);
+    }
+
+    #[test]
+    fn denoise_in_color_space_returns_error_if_lambda_is_not_positive() {
+        let test_array = Array3::<f64>::zeros((10, 5, 3));
+        let image_array = ImageArray::from(&test_array);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        for (luma_lambda, chroma_lambda) in [
+            (0.0, 0.01),
+            (0.1, 0.0),
+            (-0.1, 0.01),
+            (0.1, f64::NAN),
+            (f64::INFINITY, 0.01),
+        ] {
+            let denoised = image_array.denoise_in_color_space(
+                ColorSpace::Lab,
+                luma_lambda,
+                chroma_lambda,
+                |image_array, lambda| {
+                    image_array.denoise_fista(lambda, 10, 0.0)
+                },
+            );
+
+            assert_eq!(denoised.unwrap_err(), unsupported_error);
+        }
+    }
+
+    const CFA_PATTERNS: [CfaPattern; 4] = [
+        CfaPattern::Rggb,
+        CfaPattern::Bggr,
+        CfaPattern::Grbg,
+        CfaPattern::Gbrg,
+    ];
+
+    /// RGB image with a sharp diagonal edge between two colors.
+    fn make_edge_array3(shape: (usize, usize)) -> Array3<f64> {
+        Array3::from_shape_fn((shape.0, shape.1, 3), |(x, y, z)| {
+            let colors = if 2 * x > y + 4 {
+                [200.0, 60.0, 30.0]
+            } else {
+                [40.0, 120.0, 220.0]
+            };
+            colors[z]
+        })
+    }
+
+    /// Raw data of the image captured through the CFA.
+    fn mosaic(array: &Array3<f64>, cfa_pattern: CfaPattern) -> Array3<f64> {
+        let (len_x, len_y, _) = array.dim();
+        Array3::from_shape_fn((len_x, len_y, 1), |(x, y, _)| {
+            array[[x, y, cfa_pattern.channel_at(x, y)]]
+        })
+    }
+
+    #[test]
+    fn cfa_pattern_channel_at() {
+        let blocks = CFA_PATTERNS.map(|cfa_pattern| {
+            [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .map(|(x, y)| cfa_pattern.channel_at(x + 4, y + 2))
+        });
+
+        assert_eq!(
+            blocks,
+            [[0, 1, 1, 2], [2, 1, 1, 0], [1, 0, 2, 1], [1, 2, 0, 1]]
+        );
+    }
+
+    #[test]
+    fn demosaic_recovers_image_better_than_bilinear_interpolation() {
+        let test_array = make_edge_array3((16, 12));
+
+        for cfa_pattern in CFA_PATTERNS {
+            let raw = mosaic(&test_array, cfa_pattern);
+            let image_array = ImageArray::from(&raw);
+
+            let demosaiced = image_array
+                .demosaic(cfa_pattern, 10.0, 1.0, 1.0, 1000, 0.0)
+                .unwrap();
+            let mask = cfa_pattern.mask(16, 12);
+            let bilinear = bilinear_interpolation(&(&mask * &raw), &mask);
+
+            let error = (&*demosaiced - &test_array).norm();
+            let bilinear_error = (&bilinear - &test_array).norm();
+            assert_eq!(demosaiced.dim(), (16, 12, 3));
+            assert!(error < 0.75 * bilinear_error);
+            // the observed colors are kept
+            let observed_error = (&mask * &(&*demosaiced - &test_array)).norm();
+            assert!(observed_error < 1e-2 * test_array.norm());
+        }
+    }
+
+    #[test]
+    fn demosaic_outputs_rgb_image_of_same_bit_depth() {
+        let raw = mosaic(&make_edge_array3((16, 12)), CfaPattern::Grbg);
+        let raw_img = ImageBuffer::from_fn(16, 12, |x, y| {
+            Luma([raw[[x as usize, y as usize, 0]] as u16 * 257])
+        });
+        let image_array = ImageArray::from(&DynamicImage::ImageLuma16(raw_img));
+
+        let demosaiced = image_array
+            .demosaic(CfaPattern::Grbg, 10.0, 1.0, 1.0, 10, 0.0)
+            .unwrap();
+
+        assert_eq!(demosaiced.into_dynamic().color(), ColorType::Rgb16);
+        assert_eq!(demosaiced.layout(), Some(Layout::Planar));
+        assert_eq!(demosaiced.lambda_map(), None);
+    }
+
+    #[test]
+    fn demosaic_returns_error_if_image_has_more_than_1_channel() {
+        let test_array = make_edge_array3((16, 12));
+        let image_array = ImageArray::from(&test_array);
+
+        let demosaiced =
+            image_array.demosaic(CfaPattern::Rggb, 1.0, 1.0, 1.0, 10, 0.0);
+
+        let incompatible_shape_error =
+            ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape);
+        assert_eq!(demosaiced.unwrap_err(), incompatible_shape_error);
+    }
+
+    #[test]
+    fn denoise_fista_returns_error_if_values_are_unobserved() {
+        let test_array = make_edge_array3((16, 12));
+        let mask = CfaPattern::Rggb.mask(16, 12);
+        let image_array =
+            ImageArray::from(&test_array).with_observation_mask(mask);
+
+        let denoised_fista = image_array.denoise_fista(1.0, 10, 0.0);
+        let denoised_adaptive =
+            image_array.denoise_adaptive(1.0, 1.0, 1.0, 10, 0.0);
+
+        let unsupported_error =
+            ShapeError::from_kind(ndarray::ErrorKind::Unsupported);
+        assert_eq!(denoised_fista.unwrap_err(), unsupported_error);
+        assert!(denoised_adaptive.unwrap().iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn bilinear_interpolation_of_bayer_pattern() {
+        let mut raw = Array3::<f64>::zeros((4, 4, 1));
+        raw.mapv_inplace(|_| rand::random::<u8>() as f64);
+        let mask = CfaPattern::Rggb.mask(4, 4);
+
+        let interpolated = bilinear_interpolation(&(&mask * &raw), &mask);
+
+        // red at green and blue pixels, and green at a blue pixel
+        assert_eq!(
+            interpolated[[1, 0, 0]],
+            (raw[[0, 0, 0]] + raw[[2, 0, 0]]) / 2.0
+        );
+        assert_eq!(
+            interpolated[[1, 1, 0]],
+            (raw[[0, 0, 0]] + raw[[2, 0, 0]] + raw[[0, 2, 0]] + raw[[2, 2, 0]])
+                / 4.0
+        );
+        assert_eq!(
+            interpolated[[1, 1, 1]],
+            (raw[[0, 1, 0]] + raw[[2, 1, 0]] + raw[[1, 0, 0]] + raw[[1, 2, 0]])
+                / 4.0
+        );
+        // at the boundary, only the neighbours inside the image
+        assert_eq!(interpolated[[3, 3, 0]], raw[[2, 2, 0]]);
+        assert_eq!(interpolated[[0, 0, 0]], raw[[0, 0, 0]]);
     }
 }